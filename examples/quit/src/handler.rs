@@ -1,7 +1,8 @@
-use serenity::{async_trait, model::prelude::*, prelude::*, Result};
+use serenity::{async_trait, model::prelude::*, prelude::*};
 use serenity_slash_command_framework::{
-    DefaultSlashCommandHandler, SlashCommandBuilder, SlashCommandCallback, SlashCommandEntry,
-    SlashCommandHandler,
+    CommandTarget, DefaultSlashCommandHandler, RegistrationReport, SlashCommandBuilder,
+    SlashCommandCallback, SlashCommandEntry, SlashCommandError, SlashCommandHandler,
+    SubcommandCallback,
 };
 use tracing::{error, info};
 
@@ -10,6 +11,12 @@ pub struct Handler {
     slash_commands: DefaultSlashCommandHandler,
 }
 
+impl Handler {
+    pub async fn manifest_json(&self) -> String {
+        self.slash_commands.manifest_json().await
+    }
+}
+
 #[async_trait]
 impl EventHandler for Handler {
     async fn cache_ready(&self, _ctx: Context, guilds: Vec<GuildId>) {
@@ -21,10 +28,13 @@ impl EventHandler for Handler {
         info!("{} is connected!", ready.user.name);
 
         info!("Initializing slash commands...");
-        if let Err(why) = self.create_application_commands(&ctx).await {
-            error!("Error while creating slash commands: {:#?}", why);
+        match self.slash_commands.register_on_ready(&ctx).await {
+            Some(report) if !report.is_success() => {
+                error!("Error while creating slash commands: {}", report);
+            }
+            Some(_) => info!("Done initializing slash commands."),
+            None => info!("Slash commands were already initialized."),
         }
-        info!("Done initializing slash commands.");
     }
 
     async fn resume(&self, _: Context, _: ResumedEvent) {
@@ -46,23 +56,50 @@ impl EventHandler for Handler {
 
 #[async_trait]
 impl SlashCommandHandler for Handler {
-    async fn get_callback(&self, name: &str) -> Option<SlashCommandCallback> {
-        self.slash_commands.get_callback(name).await
+    async fn get_callback(
+        &self,
+        name: &str,
+        guild_id: Option<GuildId>,
+    ) -> Option<SlashCommandCallback> {
+        self.slash_commands.get_callback(name, guild_id).await
     }
 
-    async fn create_slash_command<F: Send>(&mut self, f: F) -> &mut Self
+    async fn get_subcommand_callback(
+        &self,
+        path: &str,
+        guild_id: Option<GuildId>,
+    ) -> Option<SubcommandCallback> {
+        self.slash_commands
+            .get_subcommand_callback(path, guild_id)
+            .await
+    }
+
+    async fn create_slash_command<F: Send>(
+        &mut self,
+        f: F,
+    ) -> std::result::Result<&mut Self, SlashCommandError>
     where
         F: FnOnce(&mut SlashCommandBuilder) -> &mut SlashCommandBuilder,
     {
-        self.slash_commands.create_slash_command(f).await;
-        self
+        self.slash_commands.create_slash_command(f).await?;
+        Ok(self)
     }
 
-    async fn create_application_commands(&self, ctx: &Context) -> Result<()> {
+    async fn create_application_commands(&self, ctx: &Context) -> RegistrationReport {
         self.slash_commands.create_application_commands(ctx).await
     }
 
-    async fn create_global_command(&self, ctx: &Context, cmd: &SlashCommandEntry) -> Result<()> {
+    async fn create_global_command(
+        &self,
+        ctx: &Context,
+        cmd: &SlashCommandEntry,
+    ) -> RegistrationReport {
         self.slash_commands.create_global_command(ctx, cmd).await
     }
+
+    async fn command_registered(&self, name: &str, target: CommandTarget, id: CommandId) {
+        self.slash_commands
+            .command_registered(name, target, id)
+            .await
+    }
 }
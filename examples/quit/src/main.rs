@@ -18,8 +18,24 @@ impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<Mutex<ShardManager>>;
 }
 
+async fn register_commands(handler: &mut handler::Handler) {
+    handler
+        .register_slash_command::<QUIT_COMMAND>()
+        .await
+        .expect("Failed to register the quit command");
+}
+
 #[tokio::main]
 async fn main() {
+    // `cargo run -- --manifest` prints the command definitions without
+    // connecting to Discord.
+    if std::env::args().any(|arg| arg == "--manifest") {
+        let mut handler = handler::Handler::default();
+        register_commands(&mut handler).await;
+        println!("{}", handler.manifest_json().await);
+        return;
+    }
+
     dotenv::dotenv().expect("Failed to load .env file");
 
     let subscriber = FmtSubscriber::builder()
@@ -49,7 +65,7 @@ async fn main() {
     let framework = StandardFramework::new().configure(|c| c.owners(owners).prefix(&prefix));
 
     let mut handler = handler::Handler::default();
-    handler.register_slash_command::<QUIT_COMMAND>().await;
+    register_commands(&mut handler).await;
 
     let application_id: u64 = std::env::var("APPLICATION_ID")
         .expect("Expected an application id in the environment. Add the `APPLICATION_ID` key to the .env file")
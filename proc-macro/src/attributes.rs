@@ -289,3 +289,53 @@ macro_rules! attr_option_num {
 }
 
 attr_option_num!(u16, u32, u64, usize);
+
+/// `#[cooldown(<bucket>, <window in seconds>[, <burst>])]`
+#[derive(Debug)]
+pub struct CooldownOption {
+    pub bucket: Ident,
+    pub window: u64,
+    pub burst: u32,
+}
+
+impl AttributeOption for CooldownOption {
+    const VALUE_KINDS: &'static [ValueKind] = &[ValueKind::List];
+
+    fn apply(_lit: Option<&Lit>) -> syn::Result<Self> {
+        unreachable!("cooldowns are parsed from the whole list")
+    }
+
+    fn parse(values: Values) -> syn::Result<Self> {
+        validate(&values, Self::VALUE_KINDS)?;
+
+        let (bucket, window, burst) = match values.literals.as_slice() {
+            [bucket, window] => (bucket, window, None),
+            [bucket, window, burst] => (bucket, window, Some(burst)),
+            _ => {
+                return Err(Error::new(
+                    values.span,
+                    "expected `#[cooldown(<bucket>, <window in seconds>[, <burst>])]`",
+                ))
+            }
+        };
+
+        let bucket = match bucket.to_str().as_str() {
+            "user" => Ident::new("User", bucket.span()),
+            "channel" => Ident::new("Channel", bucket.span()),
+            "guild" => Ident::new("Guild", bucket.span()),
+            "global" => Ident::new("Global", bucket.span()),
+            _ => {
+                return Err(Error::new(
+                    bucket.span(),
+                    "the bucket must be one of `user`, `channel`, `guild` or `global`",
+                ))
+            }
+        };
+
+        Ok(CooldownOption {
+            bucket,
+            window: u64::apply(Some(window))?,
+            burst: burst.map_or(Ok(1), |burst| u32::apply(Some(burst)))?,
+        })
+    }
+}
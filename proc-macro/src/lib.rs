@@ -12,9 +12,9 @@ use constants::*;
 use structures::*;
 use utils::*;
 
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::spanned::Spanned;
-use syn::{parse_macro_input, parse_quote, Error, Ident, Lit};
+use syn::{parse_macro_input, parse_quote, Error, Ident, Lit, Path};
 
 #[proc_macro_attribute]
 pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
@@ -30,6 +30,14 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
 
     for attr in &fun.attributes {
         let span = attr.span();
+
+        // Checks are paths, which `parse_values` does not accept.
+        if attr.path.is_ident("check") {
+            let check: Path = propagate_err!(attr.parse_args());
+            options.checks.push(check);
+            continue;
+        }
+
         let values = propagate_err!(parse_values(attr));
 
         let name = values.name.to_string();
@@ -51,6 +59,10 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
                 let permission_name: Ident = propagate_err!(attributes::parse(values));
                 options.permissions.push(permission_name);
             }
+            "cooldown" => {
+                let cooldown: CooldownOption = propagate_err!(attributes::parse(values));
+                options.cooldown = Some(cooldown);
+            }
             "option" => {
                 let option_name: Ident = propagate_err!(attributes::parse(values));
                 options.options.push(option_name);
@@ -69,6 +81,8 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
         default_permission,
         permissions,
         options,
+        checks,
+        cooldown,
     } = options;
 
     propagate_err!(create_declaration_validations(&mut fun));
@@ -92,6 +106,7 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
     let create_application_command_path = quote!(serenity::builder::CreateApplicationCommand);
     let create_application_command_permissions_data_path =
         quote!(serenity::builder::CreateApplicationCommandPermissionsData);
+    let deferred_permissions_path = quote!(serenity_slash_command_framework::DeferredPermissions);
 
     let guild_ids = if guild_ids.is_empty() {
         quote! { None }
@@ -127,9 +142,67 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
                 #permission_function
                 p
             }
+
+            fn deferred_permissions(p: &mut #deferred_permissions_path) -> &mut #deferred_permissions_path {
+                #(#permissions::apply_deferred(p);)*
+                p
+            }
         };
     }
 
+    let mut check_function = quote!();
+
+    if !checks.is_empty() {
+        let check_path = quote!(serenity_slash_command_framework::Check);
+        let check_result_path = quote!(serenity_slash_command_framework::CheckResult);
+        let context_path = quote!(serenity::client::Context);
+        let interaction_path = quote!(
+            serenity::model::interactions::application_command::ApplicationCommandInteraction
+        );
+        let box_future_path = quote!(serenity::futures::future::BoxFuture);
+
+        let check_names: Vec<_> = (0..checks.len())
+            .map(|i| format_ident!("check_{}", i))
+            .collect();
+
+        check_function = quote! {
+            fn checks() -> Vec<#check_path> {
+                #(
+                    fn #check_names<'fut>(
+                        ctx: &'fut #context_path,
+                        interaction: &'fut #interaction_path,
+                    ) -> #box_future_path<'fut, #check_result_path> {
+                        Box::pin(#checks(ctx, interaction))
+                    }
+                )*
+
+                vec![#(#check_names as #check_path),*]
+            }
+        };
+    }
+
+    let cooldown_function = match cooldown {
+        Some(CooldownOption {
+            bucket,
+            window,
+            burst,
+        }) => {
+            let cooldown_path = quote!(serenity_slash_command_framework::Cooldown);
+            let bucket_path = quote!(serenity_slash_command_framework::CooldownBucket);
+
+            quote! {
+                fn cooldown() -> Option<#cooldown_path> {
+                    Some(#cooldown_path::new(
+                        #bucket_path::#bucket,
+                        std::time::Duration::from_secs(#window),
+                        #burst,
+                    ))
+                }
+            }
+        }
+        None => quote!(),
+    };
+
     populate_fut_lifetimes_on_refs(&mut fun.args);
     let args = fun.args;
 
@@ -148,6 +221,10 @@ pub fn slash_command(attr: TokenStream, input: TokenStream) -> TokenStream {
 
             #permission_function
 
+            #check_function
+
+            #cooldown_function
+
             #(#cooked)*
             async fn callback(#(#args),*) -> #ret {
                 #(#body)*
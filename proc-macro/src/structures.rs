@@ -1,10 +1,12 @@
+use crate::attributes::CooldownOption;
 use crate::utils::{Argument, AsOption};
 
 use crate::utils::Parenthesized;
 use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
 use syn::{
-    braced, Attribute, Block, Error, FnArg, Ident, Pat, ReturnType, Stmt, Token, Type, Visibility,
+    braced, Attribute, Block, Error, FnArg, Ident, Pat, Path, ReturnType, Stmt, Token, Type,
+    Visibility,
 };
 
 fn parse_argument(arg: FnArg) -> syn::Result<Argument> {
@@ -133,6 +135,8 @@ pub struct Options {
     pub default_permission: bool,
     pub permissions: Vec<Ident>,
     pub options: Vec<Ident>,
+    pub checks: Vec<Path>,
+    pub cooldown: Option<CooldownOption>,
 }
 
 impl Options {
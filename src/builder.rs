@@ -1,6 +1,12 @@
 use crate::handler::SlashCommandEntry;
-use crate::SlashCommandCallback;
-use serenity::builder::{CreateApplicationCommand, CreateApplicationCommandPermissionsData};
+use crate::{
+    Check, CommandCallbacks, Cooldown, DeferredPermissions, SlashCommandCallback,
+    SubcommandCallback,
+};
+use serenity::{
+    builder::{CreateApplicationCommand, CreateApplicationCommandPermissionsData},
+    model::id::GuildId,
+};
 
 macro_rules! builder_fn {
     ($name:ident : $ty:ty) => {
@@ -14,16 +20,48 @@ macro_rules! builder_fn {
 #[derive(Default)]
 pub struct SlashCommandBuilder {
     name: Option<&'static str>,
-    guilds: Option<&'static [u64]>,
+    guilds: Option<Vec<GuildId>>,
     callback: Option<SlashCommandCallback>,
     create: Option<CreateApplicationCommand>,
     permissions: Option<CreateApplicationCommandPermissionsData>,
+    deferred_permissions: DeferredPermissions,
+    subcommands: Vec<(String, SubcommandCallback)>,
+    checks: Vec<Check>,
+    cooldown: Option<Cooldown>,
 }
 
 impl SlashCommandBuilder {
     builder_fn!(name: &'static str);
-    builder_fn!(guilds: &'static [u64]);
     builder_fn!(callback: SlashCommandCallback);
+    builder_fn!(cooldown: Cooldown);
+
+    pub fn guilds<I, G>(&mut self, guilds: I) -> &mut Self
+    where
+        I: IntoIterator<Item = G>,
+        G: Into<GuildId>,
+    {
+        self.guilds = Some(guilds.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Handles a subcommand, given by its path below the command such as
+    /// `user ban`. A command with subcommands does not need a callback of
+    /// its own.
+    pub fn subcommand<S: Into<String>>(
+        &mut self,
+        path: S,
+        callback: SubcommandCallback,
+    ) -> &mut Self {
+        self.subcommands.push((path.into(), callback));
+        self
+    }
+
+    /// Adds a check that has to pass before the command or any of its
+    /// subcommands runs.
+    pub fn check(&mut self, check: Check) -> &mut Self {
+        self.checks.push(check);
+        self
+    }
 
     pub fn create_application_command<F>(&mut self, f: F) -> &mut Self
     where
@@ -47,7 +85,15 @@ impl SlashCommandBuilder {
         self
     }
 
-    pub fn build(self) -> (SlashCommandEntry, SlashCommandCallback) {
+    pub fn create_deferred_permissions<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut DeferredPermissions) -> &mut DeferredPermissions,
+    {
+        f(&mut self.deferred_permissions);
+        self
+    }
+
+    pub fn build(self) -> (SlashCommandEntry, CommandCallbacks) {
         macro_rules! check_uninit {
             ($name:ident) => {
                 match self.$name {
@@ -63,9 +109,19 @@ impl SlashCommandBuilder {
 
         let name = check_uninit!(name);
         let guilds = self.guilds;
-        let callback = check_uninit!(callback);
+        let callback = if self.subcommands.is_empty() {
+            Some(check_uninit!(callback))
+        } else {
+            self.callback
+        };
+        let subcommands = self
+            .subcommands
+            .into_iter()
+            .map(|(path, callback)| (format!("{} {}", name, path), callback))
+            .collect();
         let create = check_uninit!(create);
         let permissions = check_uninit!(permissions);
+        let deferred_permissions = self.deferred_permissions;
 
         (
             SlashCommandEntry {
@@ -73,8 +129,14 @@ impl SlashCommandBuilder {
                 guilds,
                 create,
                 permissions,
+                deferred_permissions,
+            },
+            CommandCallbacks {
+                callback,
+                subcommands,
+                checks: self.checks,
+                cooldown: self.cooldown,
             },
-            callback,
         )
     }
 }
@@ -0,0 +1,335 @@
+use crate::{
+    handler::CommandIds,
+    permissions::{remote_permissions, resolve_permissions},
+    CommandTarget, RegistrationOutcome, RegistrationReport, SlashCommandEntry, SyncedCommand,
+    UnresolvedRole,
+};
+use serenity::{
+    builder::CreateApplicationCommand,
+    client::Context,
+    futures::{stream, StreamExt},
+    model::{
+        id::{CommandId, GuildId},
+        interactions::application_command::ApplicationCommand,
+    },
+    Result,
+};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+pub(crate) fn group_by_target(
+    entries: &[SlashCommandEntry],
+) -> BTreeMap<CommandTarget, Vec<&SlashCommandEntry>> {
+    let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for entry in entries {
+        for target in entry.targets() {
+            groups.entry(target).or_default().push(entry);
+        }
+    }
+    groups
+}
+
+// Overwriting a target replaces every command in it, so commands owned by other
+// tooling are fetched first and sent back along with the local ones.
+async fn kept_commands(
+    ctx: &Context,
+    target: CommandTarget,
+    keep: &HashSet<String>,
+) -> Result<Vec<CreateApplicationCommand>> {
+    if keep.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let kept = target
+        .get_commands(ctx)
+        .await?
+        .into_iter()
+        .filter(|command| keep.contains(&command.name))
+        .map(|command| {
+            let mut create = CreateApplicationCommand::default();
+            create
+                .name(&command.name)
+                .description(&command.description)
+                .default_permission(command.default_permission);
+            create.0.insert("type", (command.kind as u8).into());
+            if !command.options.is_empty() {
+                create.0.insert(
+                    "options",
+                    serde_json::to_value(&command.options).unwrap_or_default(),
+                );
+            }
+            create
+        })
+        .collect();
+
+    Ok(kept)
+}
+
+/// The remote commands that overwriting `target` sends back: the kept ones,
+/// and the local ones that are invalid, which are not sent themselves.
+pub(crate) fn kept_names(
+    target: CommandTarget,
+    keep: &HashSet<String>,
+    invalid: &[SlashCommandEntry],
+) -> HashSet<String> {
+    let mut kept = keep.clone();
+    kept.extend(
+        invalid
+            .iter()
+            .filter(|entry| entry.targets().contains(&target))
+            .map(|entry| entry.name.to_string()),
+    );
+    kept
+}
+
+#[derive(Clone, Copy)]
+struct Permissions<'a> {
+    name: &'a str,
+    target: CommandTarget,
+    command_id: CommandId,
+    cmd: &'a SlashCommandEntry,
+}
+
+/// Overwrites the targets of `entries`, and replaces the commands of the
+/// `removed` targets, which no longer have local commands, with the kept ones.
+/// The remote copies of `invalid` entries are kept as well, instead of being
+/// deleted.
+pub(crate) async fn overwrite_application_commands(
+    ctx: &Context,
+    entries: &[SlashCommandEntry],
+    invalid: &[SlashCommandEntry],
+    removed: &BTreeSet<CommandTarget>,
+    keep: &HashSet<String>,
+    command_ids: &mut CommandIds,
+    concurrency: usize,
+) -> RegistrationReport {
+    let mut report = RegistrationReport::default();
+    let mut guild_permissions: BTreeMap<GuildId, Vec<Permissions<'_>>> = BTreeMap::new();
+    let mut global_permissions = Vec::new();
+    let mut local_ids: BTreeMap<CommandTarget, HashSet<CommandId>> = BTreeMap::new();
+
+    let groups = group_by_target(entries);
+    let kept: BTreeMap<_, _> = groups
+        .keys()
+        .chain(removed)
+        .map(|&target| (target, kept_names(target, keep, invalid)))
+        .collect();
+    let kept = &kept;
+
+    // The futures are created up front instead of inside the stream, which
+    // keeps the higher-ranked closure out of the `Send` future.
+    let overwrites: Vec<_> = groups
+        .into_iter()
+        .map(|(target, cmds)| async move {
+            let created = overwrite_target(ctx, target, &cmds, &kept[&target]).await;
+            (target, cmds, created)
+        })
+        .collect();
+    let overwritten: Vec<_> = stream::iter(overwrites)
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    for (target, cmds, created) in overwritten {
+        let created = match created {
+            Ok(created) => created,
+            Err(why) => {
+                let outcome = RegistrationOutcome::from(why);
+                for cmd in &cmds {
+                    report.push(cmd.name, target, outcome.clone());
+                }
+                continue;
+            }
+        };
+
+        local_ids.insert(
+            target,
+            created
+                .iter()
+                .filter(|command| cmds.iter().any(|cmd| cmd.name == command.name))
+                .map(|command| command.id)
+                .collect(),
+        );
+
+        for cmd in &cmds {
+            let command = match created.iter().find(|command| command.name == cmd.name) {
+                Some(command) => command,
+                None => {
+                    report.push(cmd.name, target, RegistrationOutcome::MissingFromResponse);
+                    continue;
+                }
+            };
+            command_ids.insert((cmd.name.to_string(), target), command.id);
+            report.push(cmd.name, target, RegistrationOutcome::Success(command.id));
+
+            if !cmd.has_permissions() {
+                continue;
+            }
+
+            let permissions = Permissions {
+                name: cmd.name,
+                target,
+                command_id: command.id,
+                cmd,
+            };
+            match target {
+                CommandTarget::Global => global_permissions.push(permissions),
+                CommandTarget::Guild(guild_id) => guild_permissions
+                    .entry(guild_id)
+                    .or_default()
+                    .push(permissions),
+            }
+        }
+    }
+
+    if !global_permissions.is_empty() {
+        for guild_id in ctx.cache.guilds().await {
+            guild_permissions
+                .entry(guild_id)
+                .or_default()
+                .extend(global_permissions.iter().copied());
+        }
+    }
+
+    // Kept commands and the commands of targets that were not overwritten,
+    // such as unchanged ones, keep their current permissions.
+    let local_ids = &local_ids;
+    let batches: Vec<_> = guild_permissions
+        .into_iter()
+        .map(|(guild_id, permissions)| async move {
+            let foreign = |command_id| {
+                ![CommandTarget::Global, CommandTarget::Guild(guild_id)]
+                    .iter()
+                    .filter_map(|target| local_ids.get(target))
+                    .any(|ids| ids.contains(&command_id))
+            };
+            let (unresolved, res) =
+                set_guild_permissions(ctx, guild_id, &permissions, foreign).await;
+            (permissions, unresolved, res)
+        })
+        .collect();
+    let results: Vec<_> = stream::iter(batches).buffered(concurrency).collect().await;
+
+    for (permissions, unresolved, res) in results {
+        report.unresolved_roles.extend(unresolved);
+        if let Err(why) = res {
+            let outcome = RegistrationOutcome::from(why);
+            for p in permissions {
+                report.fail(p.name, p.target, outcome.clone());
+            }
+        }
+    }
+
+    let clears: Vec<_> = removed
+        .iter()
+        .map(|&target| clear_target(ctx, target, &kept[&target]))
+        .collect();
+    let cleared: Vec<_> = stream::iter(clears).buffered(concurrency).collect().await;
+    for res in cleared {
+        match res {
+            Ok(pruned) => report.pruned.extend(pruned),
+            Err(why) => {
+                if report.prune_error.is_none() {
+                    report.prune_error = Some(why);
+                }
+            }
+        }
+    }
+
+    report
+}
+
+/// Overwrites a target without local commands, returning the commands that
+/// were removed from it.
+async fn clear_target(
+    ctx: &Context,
+    target: CommandTarget,
+    keep: &HashSet<String>,
+) -> Result<Vec<SyncedCommand>> {
+    let removed: Vec<_> = target
+        .get_commands(ctx)
+        .await?
+        .into_iter()
+        .filter(|command| !keep.contains(&command.name))
+        .map(|command| SyncedCommand {
+            name: command.name,
+            target,
+        })
+        .collect();
+
+    if !removed.is_empty() {
+        overwrite_target(ctx, target, &[], keep).await?;
+    }
+
+    Ok(removed)
+}
+
+/// Sets the permissions of the local commands of a guild in one batch, along
+/// with the current ones of the commands `keep` matches, which the batch
+/// would otherwise erase.
+async fn set_guild_permissions(
+    ctx: &Context,
+    guild_id: GuildId,
+    permissions: &[Permissions<'_>],
+    keep: impl Fn(CommandId) -> bool,
+) -> (Vec<UnresolvedRole>, Result<()>) {
+    let mut data = Vec::with_capacity(permissions.len());
+    let mut unresolved = Vec::new();
+    for p in permissions {
+        let (resolved, missing) = resolve_permissions(ctx, p.cmd, guild_id).await;
+        unresolved.extend(missing);
+        data.push((p.command_id, resolved));
+    }
+
+    match remote_permissions(ctx, guild_id, keep).await {
+        Ok(remote) => data.extend(remote),
+        Err(why) => return (unresolved, Err(why)),
+    }
+
+    let res = guild_id
+        .set_application_commands_permissions(ctx, move |p| {
+            for (command_id, data) in data {
+                p.create_application_command(|c| {
+                    c.0 = data.0;
+                    c.id(command_id.0)
+                });
+            }
+            p
+        })
+        .await
+        .map(|_| ());
+
+    (unresolved, res)
+}
+
+async fn overwrite_target(
+    ctx: &Context,
+    target: CommandTarget,
+    cmds: &[&SlashCommandEntry],
+    keep: &HashSet<String>,
+) -> Result<Vec<ApplicationCommand>> {
+    let mut creates: Vec<_> = cmds.iter().map(|cmd| cmd.create.clone()).collect();
+    creates.extend(
+        kept_commands(ctx, target, keep)
+            .await?
+            .into_iter()
+            .filter(|kept| {
+                !cmds
+                    .iter()
+                    .any(|cmd| kept.0.get("name") == Some(&cmd.name.into()))
+            }),
+    );
+
+    match target {
+        CommandTarget::Global => {
+            ApplicationCommand::set_global_application_commands(ctx, |c| {
+                c.set_application_commands(creates)
+            })
+            .await
+        }
+        CommandTarget::Guild(guild_id) => {
+            guild_id
+                .set_application_commands(ctx, |c| c.set_application_commands(creates))
+                .await
+        }
+    }
+}
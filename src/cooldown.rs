@@ -0,0 +1,301 @@
+use serenity::model::interactions::application_command::ApplicationCommandInteraction;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// Who shares the uses of a command before it goes on cooldown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CooldownBucket {
+    User,
+    Channel,
+    /// Uses in direct messages count per channel.
+    Guild,
+    Global,
+}
+
+impl CooldownBucket {
+    fn key(self, interaction: &ApplicationCommandInteraction) -> u64 {
+        match self {
+            CooldownBucket::User => interaction.user.id.0,
+            CooldownBucket::Channel => interaction.channel_id.0,
+            CooldownBucket::Guild => interaction
+                .guild_id
+                .map_or(interaction.channel_id.0, |guild_id| guild_id.0),
+            CooldownBucket::Global => 0,
+        }
+    }
+}
+
+/// Allows `burst` uses per bucket within any `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cooldown {
+    bucket: CooldownBucket,
+    window: Duration,
+    burst: u32,
+}
+
+impl Cooldown {
+    /// A `burst` of 0 allows a single use, like 1.
+    pub fn new(bucket: CooldownBucket, window: Duration, burst: u32) -> Self {
+        Self {
+            bucket,
+            window,
+            burst: burst.max(1),
+        }
+    }
+
+    pub fn bucket(&self) -> CooldownBucket {
+        self.bucket
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+}
+
+/// The time source of cooldowns, which tests can replace to control time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// How often buckets whose uses all expired are dropped.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+type Key = (String, CooldownBucket, u64);
+
+#[derive(Debug)]
+struct Uses {
+    window: Duration,
+    times: VecDeque<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    uses: HashMap<Key, Uses>,
+    last_sweep: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Cooldowns {
+    state: Mutex<State>,
+}
+
+impl Cooldowns {
+    /// Records a use of the command, or returns how long to wait if the
+    /// bucket has no uses left.
+    pub(crate) async fn hit(
+        &self,
+        name: &str,
+        cooldown: Cooldown,
+        interaction: &ApplicationCommandInteraction,
+        now: Instant,
+    ) -> Option<Duration> {
+        let key = (
+            name.to_string(),
+            cooldown.bucket,
+            cooldown.bucket.key(interaction),
+        );
+        self.hit_key(key, cooldown, now).await
+    }
+
+    async fn hit_key(&self, key: Key, cooldown: Cooldown, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().await;
+        state.sweep(now);
+
+        let uses = state.uses.entry(key).or_insert_with(|| Uses {
+            window: cooldown.window,
+            times: VecDeque::new(),
+        });
+        uses.window = cooldown.window;
+        uses.expire(now);
+
+        if uses.times.len() >= cooldown.burst as usize {
+            let oldest = *uses.times.front()?;
+            return Some(cooldown.window - now.saturating_duration_since(oldest));
+        }
+
+        uses.times.push_back(now);
+        None
+    }
+}
+
+impl Uses {
+    fn expire(&mut self, now: Instant) {
+        while self
+            .times
+            .front()
+            .is_some_and(|&used| now.saturating_duration_since(used) >= self.window)
+        {
+            self.times.pop_front();
+        }
+    }
+}
+
+impl State {
+    /// Drops the buckets without unexpired uses, so that per user buckets do
+    /// not grow with every user that ever used a command.
+    fn sweep(&mut self, now: Instant) {
+        if self
+            .last_sweep
+            .is_some_and(|last| now.saturating_duration_since(last) < SWEEP_INTERVAL)
+        {
+            return;
+        }
+        self.last_sweep = Some(now);
+
+        self.uses.retain(|_, uses| {
+            uses.expire(now);
+            !uses.times.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A clock that only moves when it is told to. Clones share the time, so
+    /// a clone can be handed to the handler and advanced from the test.
+    #[derive(Clone)]
+    pub(crate) struct FakeClock(Arc<std::sync::Mutex<Instant>>);
+
+    impl FakeClock {
+        pub(crate) fn new() -> Self {
+            Self(Arc::new(std::sync::Mutex::new(Instant::now())))
+        }
+
+        pub(crate) fn advance(&self, seconds: u64) {
+            *self.0.lock().unwrap() += Duration::from_secs(seconds);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn key(id: u64) -> Key {
+        ("ping".to_string(), CooldownBucket::User, id)
+    }
+
+    fn cooldown(window: u64, burst: u32) -> Cooldown {
+        Cooldown::new(CooldownBucket::User, Duration::from_secs(window), burst)
+    }
+
+    #[tokio::test]
+    async fn allows_a_burst_of_uses() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 3);
+
+        for _ in 0..3 {
+            assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        }
+        assert!(cooldowns
+            .hit_key(key(1), cooldown, clock.now())
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn reports_the_remaining_time() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 1);
+
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        clock.advance(4);
+        assert_eq!(
+            cooldowns.hit_key(key(1), cooldown, clock.now()).await,
+            Some(Duration::from_secs(6))
+        );
+        clock.advance(5);
+        assert_eq!(
+            cooldowns.hit_key(key(1), cooldown, clock.now()).await,
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn uses_expire_after_the_window() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 2);
+
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        clock.advance(3);
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+
+        // Only the first use has expired, so the window slides by one use.
+        clock.advance(7);
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        assert_eq!(
+            cooldowns.hit_key(key(1), cooldown, clock.now()).await,
+            Some(Duration::from_secs(3))
+        );
+
+        clock.advance(10);
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+    }
+
+    #[tokio::test]
+    async fn buckets_are_independent() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 1);
+
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        assert_eq!(cooldowns.hit_key(key(2), cooldown, clock.now()).await, None);
+        assert!(cooldowns
+            .hit_key(key(1), cooldown, clock.now())
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn zero_burst_allows_one_use() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 0);
+
+        assert_eq!(cooldown.burst(), 1);
+        assert_eq!(cooldowns.hit_key(key(1), cooldown, clock.now()).await, None);
+        assert!(cooldowns
+            .hit_key(key(1), cooldown, clock.now())
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_buckets_are_swept() {
+        let clock = FakeClock::new();
+        let cooldowns = Cooldowns::default();
+        let cooldown = cooldown(10, 1);
+
+        for id in 0..100 {
+            cooldowns.hit_key(key(id), cooldown, clock.now()).await;
+        }
+        assert_eq!(cooldowns.state.lock().await.uses.len(), 100);
+
+        clock.advance(SWEEP_INTERVAL.as_secs());
+        cooldowns.hit_key(key(100), cooldown, clock.now()).await;
+        assert_eq!(cooldowns.state.lock().await.uses.len(), 1);
+    }
+}
@@ -1,9 +1,95 @@
+use serenity::{
+    client::Context,
+    futures::future::BoxFuture,
+    model::{
+        channel::MessageFlags,
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+};
 use thiserror::Error;
 
+const ERROR_MESSAGE: &str = "Something went wrong while running this command.";
+const UNAVAILABLE_MESSAGE: &str = "This command is no longer available.";
+
 #[derive(Error, Debug)]
 pub enum SlashCommandError {
     #[error("Unhandled slash command with name '{0}'")]
     UnhandledSlashCommand(String),
+    #[error("Slash command '{name}' is already registered for {existing}, cannot register it again for {new}")]
+    DuplicateSlashCommand {
+        name: String,
+        existing: String,
+        new: String,
+    },
+    #[error("Invalid guild targets for '{name}': {reason}")]
+    InvalidGuildTargets { name: String, reason: String },
+    #[error("{0} must be set before any slash command is added")]
+    TargetsAfterCommands(String),
+    #[error("Invalid command manifest: {0}")]
+    InvalidManifest(String),
+    #[error("Slash command '{0}' is defined in the manifest but has no callback")]
+    MissingCallback(String),
+    #[error("Callback '{0}' has no slash command defined in the manifest")]
+    MissingManifestEntry(String),
     #[error("{0}")]
     SerenityError(#[from] serenity::Error),
 }
+
+/// The default error handler. Tells the user that the command failed in an
+/// ephemeral message, by editing the response if the command deferred it,
+/// or in a follow-up if the command already responded.
+pub async fn respond_with_error(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    _error: &SlashCommandError,
+) -> serenity::Result<()> {
+    if let Ok(response) = interaction.get_interaction_response(ctx).await {
+        let deferred = response
+            .flags
+            .is_some_and(|flags| flags.contains(MessageFlags::LOADING));
+        if deferred {
+            interaction
+                .edit_original_interaction_response(ctx, |r| r.content(ERROR_MESSAGE))
+                .await?;
+        } else {
+            // Keep the response the command already sent.
+            interaction
+                .create_followup_message(ctx, |m| {
+                    m.content(ERROR_MESSAGE)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+                .await?;
+        }
+        return Ok(());
+    }
+
+    reply_ephemeral(ctx, interaction, ERROR_MESSAGE).await
+}
+
+/// A fallback for unknown commands that tells the user, in an ephemeral
+/// message, that the command is no longer available.
+pub fn command_unavailable<'fut>(
+    ctx: &'fut Context,
+    interaction: &'fut ApplicationCommandInteraction,
+) -> BoxFuture<'fut, serenity::Result<()>> {
+    Box::pin(reply_ephemeral(ctx, interaction, UNAVAILABLE_MESSAGE))
+}
+
+pub(crate) async fn reply_ephemeral(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    content: &str,
+) -> serenity::Result<()> {
+    interaction
+        .create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content(content)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await
+}
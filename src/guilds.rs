@@ -0,0 +1,198 @@
+use crate::SlashCommandError;
+use serde_json::Value;
+use serenity::model::id::GuildId;
+use std::{collections::HashMap, env, fmt, fs, path::Path, sync::Arc};
+
+/// Where a command should be registered, overriding the guilds it was
+/// defined with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuildOverride {
+    Global,
+    Guilds(Vec<GuildId>),
+}
+
+impl GuildOverride {
+    pub(crate) fn into_guilds(self) -> Option<Vec<GuildId>> {
+        match self {
+            GuildOverride::Global => None,
+            GuildOverride::Guilds(guilds) => Some(guilds),
+        }
+    }
+
+    /// Parses either `global` or a comma separated list of guild IDs. An empty
+    /// list is an error, since the command would not be registered anywhere.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("global") {
+            return Ok(GuildOverride::Global);
+        }
+
+        let guilds = value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse()
+                    .map(GuildId)
+                    .map_err(|_| format!("'{}' is not a guild ID", id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if guilds.is_empty() {
+            return Err("expected \"global\" or a list of guild IDs, found nothing".to_string());
+        }
+
+        Ok(GuildOverride::Guilds(guilds))
+    }
+
+    pub(crate) fn from_json(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::String(s) => Self::parse(s),
+            Value::Array(ids) if !ids.is_empty() => ids
+                .iter()
+                .map(|id| {
+                    match id {
+                        Value::Number(n) => n.as_u64().map(GuildId),
+                        Value::String(s) => s.parse().ok().map(GuildId),
+                        _ => None,
+                    }
+                    .ok_or_else(|| format!("{} is not a guild ID", id))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(GuildOverride::Guilds),
+            _ => Err(format!(
+                "expected \"global\" or a list of guild IDs, found {}",
+                value
+            )),
+        }
+    }
+}
+
+type Resolve = dyn Fn(&str) -> Result<Option<GuildOverride>, String> + Send + Sync;
+
+/// Supplies the guilds of commands at runtime. Commands the source has no
+/// entry for keep the guilds they were defined with.
+#[derive(Clone)]
+pub struct GuildTargets(Arc<Resolve>);
+
+impl GuildTargets {
+    pub fn from_fn<F>(f: F) -> Self
+    where
+        F: Fn(&str) -> Option<GuildOverride> + Send + Sync + 'static,
+    {
+        Self(Arc::new(move |name| Ok(f(name))))
+    }
+
+    /// Reads `{prefix}_{NAME}` for each command, with the name uppercased and
+    /// dashes replaced by underscores, falling back to `{prefix}` itself.
+    /// Values are `global` or a comma separated list of guild IDs, and empty
+    /// ones count as unset.
+    pub fn from_env<S: Into<String>>(prefix: S) -> Self {
+        let prefix = prefix.into();
+        Self(Arc::new(move |name| {
+            let key = format!("{}_{}", prefix, name.to_uppercase().replace('-', "_"));
+            let var = |key: &str| env::var(key).ok().filter(|value| !value.trim().is_empty());
+            match var(&key).or_else(|| var(&prefix)) {
+                Some(value) => GuildOverride::parse(&value).map(Some),
+                None => Ok(None),
+            }
+        }))
+    }
+
+    /// Loads a JSON object mapping command names to `"global"` or a list of
+    /// guild IDs. The `"*"` key applies to every command not listed.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SlashCommandError> {
+        let path = path.as_ref();
+        let invalid = |reason: String| SlashCommandError::InvalidGuildTargets {
+            name: path.display().to_string(),
+            reason,
+        };
+
+        let contents = fs::read_to_string(path).map_err(|why| invalid(why.to_string()))?;
+        let value: Value =
+            serde_json::from_str(&contents).map_err(|why| invalid(why.to_string()))?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| invalid("expected an object".to_string()))?;
+
+        let targets = object
+            .iter()
+            .map(|(name, value)| {
+                GuildOverride::from_json(value)
+                    .map(|targets| (name.clone(), targets))
+                    .map_err(|reason| invalid(format!("{}: {}", name, reason)))
+            })
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(Self(Arc::new(move |name| {
+            Ok(targets.get(name).or_else(|| targets.get("*")).cloned())
+        })))
+    }
+
+    /// Falls back to another source for commands this one has no entry for.
+    pub fn or(self, other: GuildTargets) -> Self {
+        Self(Arc::new(move |name| match (self.0)(name)? {
+            Some(targets) => Ok(Some(targets)),
+            None => (other.0)(name),
+        }))
+    }
+
+    pub fn resolve(&self, name: &str) -> Result<Option<GuildOverride>, SlashCommandError> {
+        (self.0)(name).map_err(|reason| SlashCommandError::InvalidGuildTargets {
+            name: name.to_string(),
+            reason,
+        })
+    }
+}
+
+impl fmt::Debug for GuildTargets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("GuildTargets").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_global_and_guild_lists() {
+        assert_eq!(GuildOverride::parse(" Global "), Ok(GuildOverride::Global));
+        assert_eq!(
+            GuildOverride::parse("1, 2,,3"),
+            Ok(GuildOverride::Guilds(vec![
+                GuildId(1),
+                GuildId(2),
+                GuildId(3)
+            ]))
+        );
+        assert!(GuildOverride::parse("1,two").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_guild_lists() {
+        assert!(GuildOverride::parse("").is_err());
+        assert!(GuildOverride::parse("  ").is_err());
+        assert!(GuildOverride::parse(" , ").is_err());
+    }
+
+    #[test]
+    fn from_env_ignores_empty_values() {
+        let prefix = "SLASH_COMMAND_GUILDS_TEST";
+        env::set_var(prefix, "1");
+        env::set_var(format!("{}_QUIT", prefix), "");
+        env::set_var(format!("{}_PING_PONG", prefix), "global");
+        let targets = GuildTargets::from_env(prefix);
+
+        assert_eq!(
+            targets.resolve("quit").unwrap(),
+            Some(GuildOverride::Guilds(vec![GuildId(1)]))
+        );
+        assert_eq!(
+            targets.resolve("ping-pong").unwrap(),
+            Some(GuildOverride::Global)
+        );
+
+        env::set_var(prefix, " ");
+        assert_eq!(targets.resolve("quit").unwrap(), None);
+    }
+}
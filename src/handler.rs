@@ -1,35 +1,72 @@
 use crate::{
-    builder::SlashCommandBuilder, error::SlashCommandError, SlashCommand, SlashCommandCallback,
+    builder::SlashCommandBuilder,
+    bulk,
+    cooldown::{Clock, Cooldowns, SystemClock},
+    error::{reply_ephemeral, SlashCommandError},
+    hash::{self, HashState},
+    manifest,
+    permissions::{remote_permissions, resolve_permissions},
+    plan::RegistrationMode,
+    respond_with_error, subcommand_path, sync, validate_entries, validation, AfterHook, BeforeHook,
+    Check, CommandCallbacks, CommandTarget, DeferredPermissions, ErrorHandler, FileHashStore,
+    GuildTargets, HashStore, PermissionsUpdate, RegistrationOutcome, RegistrationPlan,
+    RegistrationReport, RemoteSnapshot, SlashCommand, SlashCommandCallback, SubcommandCallback,
+    SyncSummary, ValidationError,
 };
+use serde_json::Value;
 use serenity::{
     async_trait,
     builder::{CreateApplicationCommand, CreateApplicationCommandPermissionsData},
     client::Context,
-    model::{id::GuildId, interactions::application_command::ApplicationCommandInteraction},
+    futures::{stream, StreamExt},
+    model::{
+        id::{CommandId, GuildId},
+        interactions::application_command::ApplicationCommandInteraction,
+    },
     Result,
 };
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::sync::Mutex;
 
+pub(crate) type CommandIds = HashMap<(String, CommandTarget), CommandId>;
+
 #[async_trait]
 pub trait SlashCommandHandler: Sized + Send + Sync {
-    async fn get_callback(&self, name: &str) -> Option<SlashCommandCallback>;
+    async fn get_callback(
+        &self,
+        name: &str,
+        guild_id: Option<GuildId>,
+    ) -> Option<SlashCommandCallback>;
 
-    async fn register_slash_command<T: SlashCommand>(&mut self) {
+    async fn register_slash_command<T: SlashCommand>(
+        &mut self,
+    ) -> std::result::Result<(), SlashCommandError> {
         T::register(self).await
     }
 
-    async fn create_slash_command<F: Send>(&mut self, f: F) -> &mut Self
+    async fn create_slash_command<F: Send>(
+        &mut self,
+        f: F,
+    ) -> std::result::Result<&mut Self, SlashCommandError>
     where
         F: FnOnce(&mut SlashCommandBuilder) -> &mut SlashCommandBuilder;
 
-    async fn create_application_commands(&self, ctx: &Context) -> Result<()>;
+    async fn create_application_commands(&self, ctx: &Context) -> RegistrationReport;
 
     async fn create_application_command(
         &self,
         ctx: &Context,
         cmd: &SlashCommandEntry,
-    ) -> Result<()> {
+    ) -> RegistrationReport {
         if cmd.guilds.is_some() {
             self.create_guild_command(ctx, cmd).await
         } else {
@@ -37,106 +74,1475 @@ pub trait SlashCommandHandler: Sized + Send + Sync {
         }
     }
 
-    async fn create_guild_command(&self, ctx: &Context, cmd: &SlashCommandEntry) -> Result<()> {
-        for &guild_id in cmd.guilds.unwrap() {
-            let guild_id = GuildId(guild_id);
+    async fn create_guild_command(
+        &self,
+        ctx: &Context,
+        cmd: &SlashCommandEntry,
+    ) -> RegistrationReport {
+        let mut report = RegistrationReport::default();
+
+        for &guild_id in cmd.guilds.iter().flatten() {
+            let target = CommandTarget::Guild(guild_id);
 
             let create = cmd.create.clone();
-            let guild_cmd = guild_id
+            let guild_cmd = match guild_id
                 .create_application_command(ctx, move |c| {
                     c.0 = create.0;
                     c
                 })
-                .await?;
+                .await
+            {
+                Ok(guild_cmd) => guild_cmd,
+                Err(why) => {
+                    report.push(cmd.name, target, why);
+                    continue;
+                }
+            };
+            self.command_registered(cmd.name, target, guild_cmd.id)
+                .await;
 
-            let permissions = cmd.permissions.clone();
-            guild_id
+            let (permissions, unresolved) = resolve_permissions(ctx, cmd, guild_id).await;
+            report.unresolved_roles.extend(unresolved);
+            let outcome = match guild_id
                 .create_application_command_permission(ctx, guild_cmd.id, move |p| {
                     p.0 = permissions.0;
                     p
                 })
-                .await?;
+                .await
+            {
+                Ok(_) => RegistrationOutcome::Success(guild_cmd.id),
+                Err(why) => why.into(),
+            };
+            report.push(cmd.name, target, outcome);
         }
-        Ok(())
+
+        report
     }
 
-    async fn create_global_command(&self, ctx: &Context, cmd: &SlashCommandEntry) -> Result<()>;
+    async fn create_global_command(
+        &self,
+        ctx: &Context,
+        cmd: &SlashCommandEntry,
+    ) -> RegistrationReport;
+
+    /// The callback for a subcommand, given by its full path such as
+    /// `admin user ban`.
+    async fn get_subcommand_callback(
+        &self,
+        _path: &str,
+        _guild_id: Option<GuildId>,
+    ) -> Option<SubcommandCallback> {
+        None
+    }
+
+    async fn command_registered(&self, _name: &str, _target: CommandTarget, _id: CommandId) {}
+
+    /// Runs before every command, given by its full path. Returning `false`
+    /// skips the command, and the `after` hooks with it. The interaction is
+    /// left unanswered then, so the hook has to respond to it itself, or
+    /// Discord tells the user that the application did not respond.
+    async fn before(
+        &self,
+        _ctx: &Context,
+        _interaction: &ApplicationCommandInteraction,
+        _path: &str,
+    ) -> bool {
+        true
+    }
+
+    /// Runs after every command with the outcome of its callback.
+    async fn after(
+        &self,
+        _ctx: &Context,
+        _interaction: &ApplicationCommandInteraction,
+        _path: &str,
+        _result: &Result<()>,
+    ) {
+    }
+
+    /// The checks of a command, which run after the `before` hooks.
+    async fn get_checks(&self, _name: &str, _guild_id: Option<GuildId>) -> Vec<Check> {
+        Vec::new()
+    }
+
+    /// Records a use of a command, returning how long the user has to wait
+    /// instead if the command is on cooldown.
+    async fn cooldown_remaining(
+        &self,
+        _interaction: &ApplicationCommandInteraction,
+    ) -> Option<Duration> {
+        None
+    }
+
+    /// Called for commands without a callback, which are usually stale
+    /// commands that are still visible after a deploy.
+    async fn unknown_command(
+        &self,
+        _ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+    ) -> std::result::Result<(), SlashCommandError> {
+        Err(SlashCommandError::UnhandledSlashCommand(
+            interaction.data.name.clone(),
+        ))
+    }
+
+    /// Handles errors of `interaction_create` before they are returned, by
+    /// default with [`respond_with_error`].
+    async fn on_error(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+        error: &SlashCommandError,
+    ) {
+        let _ = respond_with_error(ctx, interaction, error).await;
+    }
 
     async fn interaction_create(
         &self,
         ctx: &Context,
         interaction: &ApplicationCommandInteraction,
     ) -> std::result::Result<(), SlashCommandError> {
-        let command_name = interaction.data.name.as_str();
-        match self.get_callback(command_name).await {
-            Some(callback) => callback(ctx, interaction).await.map_err(Into::into),
-            None => Err(SlashCommandError::UnhandledSlashCommand(
-                command_name.to_string(),
-            )),
+        let result = self.run_command(ctx, interaction).await;
+        if let Err(why) = &result {
+            self.on_error(ctx, interaction, why).await;
         }
+        result
     }
+
+    /// Looks up the callback of an interaction and runs it between the
+    /// `before` and `after` hooks.
+    async fn run_command(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+    ) -> std::result::Result<(), SlashCommandError> {
+        let (path, options) = subcommand_path(&interaction.data);
+        let callback = match self
+            .get_subcommand_callback(&path, interaction.guild_id)
+            .await
+        {
+            Some(callback) => Callback::Subcommand(callback),
+            None => {
+                let command_name = interaction.data.name.as_str();
+                match self.get_callback(command_name, interaction.guild_id).await {
+                    Some(callback) => Callback::Command(callback),
+                    None => return self.unknown_command(ctx, interaction).await,
+                }
+            }
+        };
+
+        if !self.before(ctx, interaction, &path).await {
+            return Ok(());
+        }
+
+        // A failed check is answered here and skips the `after` hooks, since
+        // the command never ran.
+        let checks = self
+            .get_checks(&interaction.data.name, interaction.guild_id)
+            .await;
+        for check in checks {
+            if let Err(reason) = check(ctx, interaction).await {
+                reply_ephemeral(ctx, interaction, &reason).await?;
+                return Ok(());
+            }
+        }
+
+        if let Some(remaining) = self.cooldown_remaining(interaction).await {
+            reply_ephemeral(ctx, interaction, &cooldown_message(remaining)).await?;
+            return Ok(());
+        }
+
+        let result = match callback {
+            Callback::Command(callback) => callback(ctx, interaction).await,
+            Callback::Subcommand(callback) => callback(ctx, interaction, options).await,
+        };
+        self.after(ctx, interaction, &path, &result).await;
+        result.map_err(Into::into)
+    }
+}
+
+fn cooldown_message(remaining: Duration) -> String {
+    // Round up so that the user never retries too early.
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    format!(
+        "This command is on cooldown, try again in {} second{}.",
+        seconds,
+        if seconds == 1 { "" } else { "s" }
+    )
+}
+
+/// The callback that handles an interaction, which is only called once the
+/// hooks, checks and cooldown let the command run.
+enum Callback {
+    Command(SlashCommandCallback),
+    Subcommand(SubcommandCallback),
 }
 
 #[derive(Debug, Clone)]
 pub struct SlashCommandEntry {
     pub name: &'static str,
-    pub guilds: Option<&'static [u64]>,
+    pub guilds: Option<Vec<GuildId>>,
     pub create: CreateApplicationCommand,
     pub permissions: CreateApplicationCommandPermissionsData,
+    pub deferred_permissions: DeferredPermissions,
+}
+
+impl SlashCommandEntry {
+    pub fn has_permissions(&self) -> bool {
+        has_permissions(&self.permissions) || !self.deferred_permissions.is_empty()
+    }
+
+    pub fn targets(&self) -> Vec<CommandTarget> {
+        match &self.guilds {
+            Some(guilds) => guilds.iter().copied().map(CommandTarget::Guild).collect(),
+            None => vec![CommandTarget::Global],
+        }
+    }
+
+    /// Whether both entries would be visible in at least one common guild.
+    /// Global commands overlap with every other command.
+    pub fn overlaps(&self, other: &SlashCommandEntry) -> bool {
+        match (&self.guilds, &other.guilds) {
+            (Some(a), Some(b)) => a.iter().any(|guild_id| b.contains(guild_id)),
+            _ => true,
+        }
+    }
+
+    fn describe_guilds(&self) -> String {
+        match &self.guilds {
+            Some(guilds) => {
+                let guilds: Vec<_> = guilds.iter().map(ToString::to_string).collect();
+                format!("guilds [{}]", guilds.join(", "))
+            }
+            None => "all guilds (global)".to_string(),
+        }
+    }
+
+    fn is_available_in(&self, guild_id: Option<GuildId>) -> bool {
+        match (&self.guilds, guild_id) {
+            (None, _) => true,
+            (Some(guilds), Some(guild_id)) => guilds.contains(&guild_id),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+fn check_duplicate(
+    existing: &[SlashCommandEntry],
+    entry: &SlashCommandEntry,
+) -> std::result::Result<(), SlashCommandError> {
+    match existing
+        .iter()
+        .find(|cmd| cmd.name == entry.name && cmd.overlaps(entry))
+    {
+        Some(cmd) => Err(SlashCommandError::DuplicateSlashCommand {
+            name: entry.name.to_string(),
+            existing: cmd.describe_guilds(),
+            new: entry.describe_guilds(),
+        }),
+        None => Ok(()),
+    }
 }
 
+type Callbacks = HashMap<&'static str, Vec<(SlashCommandEntry, CommandCallbacks)>>;
+
 pub struct DefaultSlashCommandHandler {
-    to_add: Option<Vec<SlashCommandEntry>>,
-    callbacks: Mutex<HashMap<&'static str, SlashCommandCallback>>,
+    to_add: Mutex<Vec<SlashCommandEntry>>,
+    callbacks: Mutex<Callbacks>,
+    bulk_overwrite: bool,
+    prune_stale: bool,
+    keep_remote: HashSet<String>,
+    command_ids: Mutex<CommandIds>,
+    concurrency: usize,
+    guild_targets: Option<GuildTargets>,
+    dev_guild: Option<GuildId>,
+    hash_store: Option<Arc<dyn HashStore>>,
+    force_registration: bool,
+    registration_shard: u64,
+    registered: AtomicBool,
+    before_hooks: Vec<BeforeHook>,
+    after_hooks: Vec<AfterHook>,
+    error_handler: Option<ErrorHandler>,
+    fallback: Option<SlashCommandCallback>,
+    prune_unknown: bool,
+    cooldowns: Cooldowns,
+    clock: Arc<dyn Clock>,
 }
 
-#[async_trait]
-impl SlashCommandHandler for DefaultSlashCommandHandler {
-    async fn get_callback(&self, name: &str) -> Option<SlashCommandCallback> {
+/// The environment variable read by
+/// [`DefaultSlashCommandHandler::dev_guild_from_env`].
+pub const DEV_GUILD_VAR: &str = "SLASH_COMMAND_DEV_GUILD";
+
+impl DefaultSlashCommandHandler {
+    /// Replaces the full set of commands of each guild, and the global set,
+    /// with a single request per target instead of creating them one by one.
+    /// Commands that fail validation are not sent, and their remote copies
+    /// are kept like the ones of [`keep_remote_command`].
+    ///
+    /// [`keep_remote_command`]: Self::keep_remote_command
+    pub fn bulk_overwrite(&mut self, bulk_overwrite: bool) -> &mut Self {
+        self.bulk_overwrite = bulk_overwrite;
+        self
+    }
+
+    /// Limits how many commands and guilds are registered at the same time.
+    /// Requests still go through serenity's rate limiter, and the report keeps
+    /// the order in which the commands were added.
+    pub fn concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Deletes remote commands whose name is not registered locally once
+    /// registration has finished.
+    pub fn prune_stale_commands(&mut self, prune_stale: bool) -> &mut Self {
+        self.prune_stale = prune_stale;
+        self
+    }
+
+    /// Excludes a remote command from pruning, synchronization and bulk
+    /// overwrites, for commands owned by other tooling.
+    pub fn keep_remote_command<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.keep_remote.insert(name.into());
+        self
+    }
+
+    /// Skips registering targets whose command definitions hash the same as
+    /// when they were last registered successfully.
+    pub fn hash_store<S: HashStore + 'static>(&mut self, store: S) -> &mut Self {
+        self.hash_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Uses a [`FileHashStore`] at the given path as the [`hash_store`].
+    ///
+    /// [`hash_store`]: Self::hash_store
+    pub fn state_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.hash_store(FileHashStore::new(path))
+    }
+
+    /// Registers every target even if its hash did not change.
+    pub fn force_registration(&mut self, force: bool) -> &mut Self {
+        self.force_registration = force;
+        self
+    }
+
+    /// Adds a hook that runs before every command, in the order they were
+    /// added. The first one to return `false` skips the command, and has to
+    /// respond to the interaction itself.
+    pub fn add_before_hook(&mut self, hook: BeforeHook) -> &mut Self {
+        self.before_hooks.push(hook);
+        self
+    }
+
+    /// Adds a hook that runs after every command, in the order they were
+    /// added.
+    pub fn add_after_hook(&mut self, hook: AfterHook) -> &mut Self {
+        self.after_hooks.push(hook);
+        self
+    }
+
+    /// Replaces the default error handler, [`respond_with_error`].
+    ///
+    /// [`respond_with_error`]: crate::respond_with_error
+    pub fn error_handler(&mut self, handler: ErrorHandler) -> &mut Self {
+        self.error_handler = Some(handler);
+        self
+    }
+
+    /// Runs for commands that have no callback, instead of failing with
+    /// [`SlashCommandError::UnhandledSlashCommand`]. See
+    /// [`command_unavailable`] for a ready-made one.
+    ///
+    /// [`command_unavailable`]: crate::command_unavailable
+    pub fn fallback(&mut self, fallback: SlashCommandCallback) -> &mut Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Deletes commands that are not registered locally from Discord when
+    /// they are used, unless they are kept with [`keep_remote_command`].
+    /// Registered commands without a callback, such as an unknown subcommand,
+    /// are never deleted, and neither are global commands while a
+    /// [`dev_guild`] is set.
+    ///
+    /// [`dev_guild`]: Self::dev_guild
+    /// [`keep_remote_command`]: Self::keep_remote_command
+    pub fn prune_unknown_commands(&mut self, prune_unknown: bool) -> &mut Self {
+        self.prune_unknown = prune_unknown;
+        self
+    }
+
+    async fn is_known(&self, name: &str, guild_id: Option<GuildId>) -> bool {
         let callbacks = self.callbacks.lock().await;
-        callbacks.get(name).copied()
+        callbacks.get(name).is_some_and(|entries| {
+            entries
+                .iter()
+                .any(|(entry, _)| entry.is_available_in(guild_id))
+        })
+    }
+
+    /// Deletes the command of an interaction, which can be a command of the
+    /// guild it was used in or a global one. Global commands are left alone
+    /// while a dev guild is set, since they belong to another deployment then.
+    async fn prune_unknown_command(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        let skip_global = self.dev_guild.is_some();
+        let command_id = interaction.data.id;
+        if let Some(guild_id) = interaction.guild_id {
+            let guild = CommandTarget::Guild(guild_id);
+            let result = guild.delete_command(ctx, command_id).await;
+            if result.is_ok() || skip_global {
+                return result;
+            }
+        }
+        if skip_global {
+            return Ok(());
+        }
+        CommandTarget::Global.delete_command(ctx, command_id).await
+    }
+
+    /// Replaces the clock that cooldowns are measured with.
+    pub fn clock<C: Clock + 'static>(&mut self, clock: C) -> &mut Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The shard that registers commands in [`register_on_ready`].
+    ///
+    /// [`register_on_ready`]: Self::register_on_ready
+    pub fn registration_shard(&mut self, shard_id: u64) -> &mut Self {
+        self.registration_shard = shard_id;
+        self
+    }
+
+    /// Registers the commands from a `ready` event, unless the event comes
+    /// from a shard other than the [`registration_shard`] or registration
+    /// already succeeded in this process. Serenity sends `ready` again after
+    /// reconnecting, which would otherwise register everything again.
+    ///
+    /// [`registration_shard`]: Self::registration_shard
+    pub async fn register_on_ready(&self, ctx: &Context) -> Option<RegistrationReport> {
+        if ctx.shard_id != self.registration_shard || self.registered.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+
+        let report = self.create_application_commands(ctx).await;
+        if !report.is_success() {
+            // Try again on the next `ready`.
+            self.registered.store(false, Ordering::Release);
+        }
+
+        Some(report)
+    }
+
+    /// Overrides the guilds of the commands, with the guilds they were defined
+    /// with as the default. Fails once a command was added, since the guilds
+    /// are resolved when a command is added.
+    pub fn guild_targets(
+        &mut self,
+        guild_targets: GuildTargets,
+    ) -> std::result::Result<&mut Self, SlashCommandError> {
+        self.ensure_no_commands("guild_targets")?;
+        self.guild_targets = Some(guild_targets);
+        Ok(self)
+    }
+
+    /// Registers every global command as a command of this guild instead,
+    /// where changes show up immediately. Pruning and synchronization leave
+    /// the global commands alone while this is set. Fails once a command was
+    /// added, like [`guild_targets`].
+    ///
+    /// [`guild_targets`]: Self::guild_targets
+    pub fn dev_guild(
+        &mut self,
+        guild_id: Option<GuildId>,
+    ) -> std::result::Result<&mut Self, SlashCommandError> {
+        self.ensure_no_commands("dev_guild")?;
+        self.dev_guild = guild_id;
+        Ok(self)
+    }
+
+    fn ensure_no_commands(&mut self, setting: &str) -> std::result::Result<(), SlashCommandError> {
+        if self.to_add.get_mut().is_empty() {
+            Ok(())
+        } else {
+            Err(SlashCommandError::TargetsAfterCommands(setting.to_string()))
+        }
     }
 
-    async fn create_slash_command<F: Send>(&mut self, f: F) -> &mut Self
+    /// Enables [`dev_guild`] if the `SLASH_COMMAND_DEV_GUILD` environment
+    /// variable holds a guild ID, and leaves registration unchanged if it is
+    /// unset or empty.
+    ///
+    /// [`dev_guild`]: Self::dev_guild
+    pub fn dev_guild_from_env(&mut self) -> std::result::Result<&mut Self, SlashCommandError> {
+        let value = match env::var(DEV_GUILD_VAR) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => return Ok(self),
+        };
+
+        let guild_id =
+            value
+                .trim()
+                .parse()
+                .map_err(|_| SlashCommandError::InvalidGuildTargets {
+                    name: DEV_GUILD_VAR.to_string(),
+                    reason: format!("'{}' is not a guild ID", value),
+                })?;
+        self.dev_guild(Some(GuildId(guild_id)))
+    }
+
+    fn apply_guild_targets(
+        &self,
+        entry: &mut SlashCommandEntry,
+    ) -> std::result::Result<(), SlashCommandError> {
+        if let Some(guild_targets) = &self.guild_targets {
+            if let Some(targets) = guild_targets.resolve(entry.name)? {
+                entry.guilds = targets.into_guilds();
+            }
+        }
+        if let (None, Some(dev_guild)) = (&entry.guilds, self.dev_guild) {
+            entry.guilds = Some(vec![dev_guild]);
+        }
+        Ok(())
+    }
+
+    fn insert_entry(
+        &mut self,
+        mut entry: SlashCommandEntry,
+        callbacks: CommandCallbacks,
+    ) -> std::result::Result<(), SlashCommandError> {
+        self.apply_guild_targets(&mut entry)?;
+        check_duplicate(self.to_add.get_mut(), &entry)?;
+        self.push_entry(entry, callbacks);
+
+        Ok(())
+    }
+
+    /// Adds an entry whose guilds are resolved and that was checked for
+    /// duplicates.
+    fn push_entry(&mut self, entry: SlashCommandEntry, callbacks: CommandCallbacks) {
+        self.callbacks
+            .get_mut()
+            .entry(entry.name)
+            .or_default()
+            .push((entry.clone(), callbacks));
+        self.to_add.get_mut().push(entry);
+    }
+
+    /// Registers the commands defined in a manifest file, see
+    /// [`load_manifest`], binding each one to the callback of the same name.
+    /// Fails without registering anything if a command has no callback, a
+    /// callback has no command, or a command is a duplicate.
+    ///
+    /// [`load_manifest`]: crate::load_manifest
+    pub fn load_manifest<P, I, S>(
+        &mut self,
+        path: P,
+        callbacks: I,
+    ) -> std::result::Result<&mut Self, SlashCommandError>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (S, SlashCommandCallback)>,
+        S: Into<String>,
+    {
+        let entries = manifest::load_manifest(path)?;
+        self.add_manifest_entries(entries, callbacks)
+    }
+
+    pub fn add_manifest_entries<I, S>(
+        &mut self,
+        entries: Vec<SlashCommandEntry>,
+        callbacks: I,
+    ) -> std::result::Result<&mut Self, SlashCommandError>
+    where
+        I: IntoIterator<Item = (S, SlashCommandCallback)>,
+        S: Into<String>,
+    {
+        let callbacks: HashMap<String, SlashCommandCallback> = callbacks
+            .into_iter()
+            .map(|(name, callback)| (name.into(), callback))
+            .collect();
+
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| !callbacks.contains_key(entry.name))
+        {
+            return Err(SlashCommandError::MissingCallback(entry.name.to_string()));
+        }
+        if let Some(name) = callbacks
+            .keys()
+            .find(|&name| !entries.iter().any(|entry| entry.name == name))
+        {
+            return Err(SlashCommandError::MissingManifestEntry(name.clone()));
+        }
+
+        // Every entry is checked before any is added, so that an error leaves
+        // the handler unchanged.
+        let mut resolved: Vec<SlashCommandEntry> = Vec::with_capacity(entries.len());
+        for mut entry in entries {
+            self.apply_guild_targets(&mut entry)?;
+            check_duplicate(self.to_add.get_mut(), &entry)?;
+            check_duplicate(&resolved, &entry)?;
+            resolved.push(entry);
+        }
+
+        for entry in resolved {
+            let callback = callbacks[entry.name];
+            self.push_entry(entry, callback.into());
+        }
+
+        Ok(self)
+    }
+
+    /// Registers the entries one by one.
+    async fn register_units(
+        &self,
+        ctx: &Context,
+        entries: &[SlashCommandEntry],
+        report: &mut RegistrationReport,
+    ) {
+        // Split every guild command into one entry per guild, so that the
+        // guilds of a single command are registered concurrently as well.
+        let units: Vec<_> = entries
+            .iter()
+            .flat_map(|cmd| match &cmd.guilds {
+                Some(guilds) => guilds
+                    .iter()
+                    .map(|&guild_id| SlashCommandEntry {
+                        guilds: Some(vec![guild_id]),
+                        ..cmd.clone()
+                    })
+                    .collect(),
+                None => vec![cmd.clone()],
+            })
+            .map(|cmd| async move { self.create_application_command(ctx, &cmd).await })
+            .collect();
+
+        let reports: Vec<_> = stream::iter(units)
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        for r in reports {
+            report.extend(r);
+        }
+    }
+
+    /// Deletes remote commands that are not in `to_add`, the full command set.
+    async fn prune_stale(
+        &self,
+        ctx: &Context,
+        to_add: &[SlashCommandEntry],
+        report: &mut RegistrationReport,
+    ) {
+        let skip_global = self.dev_guild.is_some();
+        match sync::prune_stale_commands(ctx, to_add, &self.keep_remote, skip_global).await {
+            Ok(pruned) => {
+                let mut command_ids = self.command_ids.lock().await;
+                for command in &pruned {
+                    command_ids.remove(&(command.name.clone(), command.target));
+                }
+                report.pruned.extend(pruned);
+            }
+            Err(why) => {
+                report.prune_error.get_or_insert(why);
+            }
+        }
+    }
+
+    pub async fn command_id(&self, name: &str, target: CommandTarget) -> Option<CommandId> {
+        let command_ids = self.command_ids.lock().await;
+        command_ids.get(&(name.to_string(), target)).copied()
+    }
+
+    pub async fn command_ids(&self) -> HashMap<(String, CommandTarget), CommandId> {
+        self.command_ids.lock().await.clone()
+    }
+
+    async fn resolve_command_id(
+        &self,
+        ctx: &Context,
+        name: &str,
+        target: CommandTarget,
+    ) -> Result<Option<CommandId>> {
+        if let Some(command_id) = self.command_id(name, target).await {
+            return Ok(Some(command_id));
+        }
+
+        let command_id = target
+            .get_commands(ctx)
+            .await?
+            .into_iter()
+            .find(|c| c.name == name)
+            .map(|c| c.id);
+        if let Some(command_id) = command_id {
+            self.command_registered(name, target, command_id).await;
+        }
+
+        Ok(command_id)
+    }
+
+    /// Replaces the locally stored permissions of every registration of a
+    /// command. Use [`update_permissions`] to push them to a guild.
+    ///
+    /// [`update_permissions`]: Self::update_permissions
+    pub async fn replace_permissions<F>(&self, name: &str, f: F)
+    where
+        F: Fn(
+            &mut CreateApplicationCommandPermissionsData,
+        ) -> &mut CreateApplicationCommandPermissionsData,
+    {
+        let mut to_add = self.to_add.lock().await;
+        for cmd in to_add.iter_mut().filter(|cmd| cmd.name == name) {
+            let mut permissions = Default::default();
+            f(&mut permissions);
+            cmd.permissions = permissions;
+        }
+    }
+
+    /// Pushes the permissions of one command, or of all commands available in
+    /// the guild, without re-creating the commands themselves. Updating all of
+    /// them keeps the permissions of commands that are not registered locally.
+    pub async fn update_permissions(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        name: Option<&str>,
+    ) -> Result<PermissionsUpdate> {
+        let cmds: Vec<_> = self
+            .to_add
+            .lock()
+            .await
+            .iter()
+            .filter(|cmd| cmd.is_available_in(Some(guild_id)))
+            .filter(|cmd| name.is_none_or(|name| cmd.name == name))
+            .cloned()
+            .collect();
+
+        let mut update = PermissionsUpdate::default();
+        let mut permissions = Vec::with_capacity(cmds.len());
+        for cmd in cmds {
+            let target = match cmd.guilds {
+                Some(_) => CommandTarget::Guild(guild_id),
+                None => CommandTarget::Global,
+            };
+            match self.resolve_command_id(ctx, cmd.name, target).await? {
+                Some(command_id) => {
+                    let (data, missing) = resolve_permissions(ctx, &cmd, guild_id).await;
+                    update.unresolved_roles.extend(missing);
+                    permissions.push((command_id, data));
+                }
+                None => update.missing_commands.push(cmd.name.to_string()),
+            }
+        }
+
+        if name.is_some() {
+            for (command_id, data) in permissions {
+                guild_id
+                    .create_application_command_permission(ctx, command_id, move |p| {
+                        p.0 = data.0;
+                        p
+                    })
+                    .await?;
+            }
+            return Ok(update);
+        }
+
+        let local: HashSet<_> = permissions.iter().map(|(id, _)| *id).collect();
+        permissions.extend(remote_permissions(ctx, guild_id, |id| !local.contains(&id)).await?);
+
+        guild_id
+            .set_application_commands_permissions(ctx, move |p| {
+                for (command_id, data) in permissions {
+                    p.create_application_command(|c| {
+                        c.0 = data.0;
+                        c.id(command_id.0)
+                    });
+                }
+                p
+            })
+            .await?;
+
+        Ok(update)
+    }
+
+    /// Adds a command to a running bot and registers it with Discord right
+    /// away, without having to restart the client.
+    pub async fn add_slash_command<F>(
+        &self,
+        ctx: &Context,
+        f: F,
+    ) -> std::result::Result<RegistrationReport, SlashCommandError>
     where
         F: FnOnce(&mut SlashCommandBuilder) -> &mut SlashCommandBuilder,
     {
         let mut builder = Default::default();
         f(&mut builder);
 
-        let (entry, callback) = builder.build();
+        let (mut entry, callback) = builder.build();
+        self.apply_guild_targets(&mut entry)?;
+
+        let mut report = RegistrationReport::default();
+        let (valid, _) = validation::reject_invalid(std::slice::from_ref(&entry), &mut report);
+        if valid.is_empty() {
+            return Ok(report);
+        }
+
+        {
+            let mut to_add = self.to_add.lock().await;
+            check_duplicate(&to_add, &entry)?;
+            to_add.push(entry.clone());
+        }
         {
             let mut callbacks = self.callbacks.lock().await;
-            callbacks.insert(entry.name, callback);
+            callbacks
+                .entry(entry.name)
+                .or_default()
+                .push((entry.clone(), callback));
         }
-        self.to_add.as_mut().unwrap().push(entry);
 
-        self
+        Ok(self.create_application_command(ctx, &entry).await)
+    }
+
+    pub async fn add_command<T: SlashCommand>(
+        &self,
+        ctx: &Context,
+    ) -> std::result::Result<RegistrationReport, SlashCommandError> {
+        self.add_slash_command(ctx, T::build).await
     }
 
-    async fn create_application_commands(&self, ctx: &Context) -> Result<()> {
-        let to_add = match self.to_add.as_ref() {
-            Some(t) => t,
-            None => return Ok(()),
+    /// Removes a command from a running bot and deletes it from Discord.
+    pub async fn remove_slash_command(&self, ctx: &Context, name: &str) -> Result<()> {
+        self.callbacks.lock().await.remove(name);
+
+        let removed: Vec<_> = {
+            let mut to_add = self.to_add.lock().await;
+            let (removed, kept) = to_add.drain(..).partition(|cmd| cmd.name == name);
+            *to_add = kept;
+            removed
         };
 
-        for cmd in to_add {
-            self.create_application_command(ctx, cmd).await?;
+        for target in removed.iter().flat_map(SlashCommandEntry::targets) {
+            let known_id = self
+                .command_ids
+                .lock()
+                .await
+                .remove(&(name.to_string(), target));
+
+            let command_id = match known_id {
+                Some(command_id) => Some(command_id),
+                None => target
+                    .get_commands(ctx)
+                    .await?
+                    .into_iter()
+                    .find(|c| c.name == name)
+                    .map(|c| c.id),
+            };
+
+            if let Some(command_id) = command_id {
+                target.delete_command(ctx, command_id).await?;
+            }
         }
 
         Ok(())
     }
 
-    async fn create_global_command(&self, _ctx: &Context, _cmd: &SlashCommandEntry) -> Result<()> {
-        todo!()
+    /// The definitions of all registered commands, see [`manifest`].
+    ///
+    /// [`manifest`]: crate::manifest
+    pub async fn manifest(&self) -> Value {
+        manifest::manifest(&self.to_add.lock().await)
+    }
+
+    pub async fn manifest_json(&self) -> String {
+        manifest::manifest_json(&self.to_add.lock().await)
+    }
+
+    pub async fn validate(&self) -> Vec<ValidationError> {
+        validate_entries(&self.to_add.lock().await)
+    }
+
+    async fn hash_state(&self, valid: &[SlashCommandEntry]) -> Option<HashState> {
+        let store = self.hash_store.as_ref()?;
+        // Global commands are only moved to the dev guild, not removed.
+        let keep_global = self.dev_guild.is_some();
+        Some(HashState::load(store.as_ref(), valid, self.force_registration, keep_global).await)
+    }
+
+    /// The snapshot without the global commands while they are registered in
+    /// the dev guild, which registration leaves alone.
+    fn visible_remote(&self, remote: &RemoteSnapshot) -> RemoteSnapshot {
+        let mut remote = remote.clone();
+        if self.dev_guild.is_some() {
+            remote.commands.remove(&CommandTarget::Global);
+        }
+        remote
+    }
+
+    /// Lists the changes [`create_application_commands`] would make with the
+    /// current settings, without sending any requests. This includes the
+    /// targets that a bulk overwrite replaces, pruning, and the targets the
+    /// hash store skips. Pass a snapshot of the remote state to diff against
+    /// it.
+    ///
+    /// [`create_application_commands`]: SlashCommandHandler::create_application_commands
+    pub async fn registration_plan(&self, remote: Option<&RemoteSnapshot>) -> RegistrationPlan {
+        let to_add = self.to_add.lock().await.clone();
+        let remote = remote.map(|remote| self.visible_remote(remote));
+
+        let (valid, invalid) =
+            validation::reject_invalid(&to_add, &mut RegistrationReport::default());
+        let hash_state = self.hash_state(&valid).await;
+        let skipped = hash_state
+            .as_ref()
+            .map(HashState::skipped)
+            .unwrap_or_default();
+        if hash_state.as_ref().is_some_and(HashState::is_unchanged) {
+            return RegistrationPlan {
+                skipped,
+                ..Default::default()
+            };
+        }
+        let (valid, removed) = match &hash_state {
+            Some(state) => (
+                hash::restrict(&valid, &state.changed),
+                state.removed.clone(),
+            ),
+            None => (valid, BTreeSet::new()),
+        };
+
+        let mode = RegistrationMode {
+            bulk_overwrite: self.bulk_overwrite,
+            prune: self.prune_stale.then(|| &to_add[..]),
+            keep: &self.keep_remote,
+            invalid: &invalid,
+            removed: &removed,
+        };
+        RegistrationPlan {
+            skipped,
+            ..RegistrationPlan::registration(&valid, remote.as_ref(), &mode)
+        }
+    }
+
+    /// Lists the changes [`sync_application_commands`] would make, without
+    /// sending any requests.
+    ///
+    /// [`sync_application_commands`]: Self::sync_application_commands
+    pub async fn sync_plan(&self, remote: &RemoteSnapshot) -> RegistrationPlan {
+        let to_add = self.to_add.lock().await;
+        let remote = self.visible_remote(remote);
+        RegistrationPlan::new(&to_add, Some(&remote), &self.keep_remote)
+    }
+
+    /// Fetches the remote state of every target that [`registration_plan`]
+    /// and [`sync_plan`] can change.
+    ///
+    /// [`registration_plan`]: Self::registration_plan
+    /// [`sync_plan`]: Self::sync_plan
+    pub async fn fetch_remote_snapshot(&self, ctx: &Context) -> Result<RemoteSnapshot> {
+        let to_add = self.to_add.lock().await.clone();
+
+        let mut extra = BTreeSet::new();
+        if self.prune_stale {
+            extra.extend(
+                ctx.cache
+                    .guilds()
+                    .await
+                    .into_iter()
+                    .map(CommandTarget::Guild),
+            );
+        }
+        if self.bulk_overwrite {
+            let (valid, _) =
+                validation::reject_invalid(&to_add, &mut RegistrationReport::default());
+            if let Some(state) = self.hash_state(&valid).await {
+                extra.extend(state.removed);
+            }
+        }
+
+        RemoteSnapshot::fetch_targets(ctx, &to_add, extra).await
+    }
+
+    /// Compares the registered commands with the ones Discord already has and
+    /// only creates, edits or deletes the commands that differ.
+    pub async fn sync_application_commands(&self, ctx: &Context) -> Result<SyncSummary> {
+        let to_add = self.to_add.lock().await.clone();
+
+        let mut command_ids = CommandIds::new();
+        let summary = sync::sync_application_commands(
+            ctx,
+            &to_add,
+            &self.keep_remote,
+            self.dev_guild.is_some(),
+            &mut command_ids,
+        )
+        .await?;
+        self.command_ids.lock().await.extend(command_ids);
+
+        Ok(summary)
     }
 }
 
+#[async_trait]
+impl SlashCommandHandler for DefaultSlashCommandHandler {
+    async fn get_callback(
+        &self,
+        name: &str,
+        guild_id: Option<GuildId>,
+    ) -> Option<SlashCommandCallback> {
+        let callbacks = self.callbacks.lock().await;
+        callbacks
+            .get(name)?
+            .iter()
+            .find(|(entry, _)| entry.is_available_in(guild_id))?
+            .1
+            .callback
+    }
+
+    async fn cooldown_remaining(
+        &self,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Option<Duration> {
+        let name = interaction.data.name.as_str();
+        let cooldown = {
+            let callbacks = self.callbacks.lock().await;
+            callbacks
+                .get(name)?
+                .iter()
+                .find(|(entry, _)| entry.is_available_in(interaction.guild_id))?
+                .1
+                .cooldown?
+        };
+
+        self.cooldowns
+            .hit(name, cooldown, interaction, self.clock.now())
+            .await
+    }
+
+    async fn get_checks(&self, name: &str, guild_id: Option<GuildId>) -> Vec<Check> {
+        let callbacks = self.callbacks.lock().await;
+        callbacks
+            .get(name)
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|(entry, _)| entry.is_available_in(guild_id))
+            })
+            .map(|(_, callbacks)| callbacks.checks.clone())
+            .unwrap_or_default()
+    }
+
+    async fn get_subcommand_callback(
+        &self,
+        path: &str,
+        guild_id: Option<GuildId>,
+    ) -> Option<SubcommandCallback> {
+        let name = path.split(' ').next()?;
+        let callbacks = self.callbacks.lock().await;
+        callbacks
+            .get(name)?
+            .iter()
+            .find(|(entry, _)| entry.is_available_in(guild_id))?
+            .1
+            .subcommands
+            .get(path)
+            .copied()
+    }
+
+    async fn create_slash_command<F: Send>(
+        &mut self,
+        f: F,
+    ) -> std::result::Result<&mut Self, SlashCommandError>
+    where
+        F: FnOnce(&mut SlashCommandBuilder) -> &mut SlashCommandBuilder,
+    {
+        let mut builder = Default::default();
+        f(&mut builder);
+
+        let (entry, callback) = builder.build();
+        self.insert_entry(entry, callback)?;
+
+        Ok(self)
+    }
+
+    async fn create_application_commands(&self, ctx: &Context) -> RegistrationReport {
+        let to_add = self.to_add.lock().await.clone();
+
+        let mut report = RegistrationReport::default();
+        let (valid, invalid) = validation::reject_invalid(&to_add, &mut report);
+
+        let hash_state = self.hash_state(&valid).await;
+        if let Some(state) = &hash_state {
+            report.skipped = state.skipped();
+            if state.is_unchanged() {
+                return report;
+            }
+        }
+        let (valid, removed) = match &hash_state {
+            Some(state) => (
+                hash::restrict(&valid, &state.changed),
+                state.removed.clone(),
+            ),
+            None => (valid, BTreeSet::new()),
+        };
+
+        if self.bulk_overwrite {
+            let mut command_ids = CommandIds::new();
+            report.extend(
+                bulk::overwrite_application_commands(
+                    ctx,
+                    &valid,
+                    &invalid,
+                    &removed,
+                    &self.keep_remote,
+                    &mut command_ids,
+                    self.concurrency,
+                )
+                .await,
+            );
+            let mut known_ids = self.command_ids.lock().await;
+            for command in &report.pruned {
+                known_ids.remove(&(command.name.clone(), command.target));
+            }
+            known_ids.extend(command_ids);
+        } else {
+            self.register_units(ctx, &valid, &mut report).await;
+        }
+
+        // A bulk overwrite only replaces the targets that still have local
+        // commands, so the other ones are pruned here as well.
+        if self.prune_stale {
+            self.prune_stale(ctx, &to_add, &mut report).await;
+        }
+
+        if let (Some(store), Some(state)) = (&self.hash_store, hash_state) {
+            let to_store = state.hashes.to_store(&report, &state.stored, &removed);
+            if let Err(why) = store.save(&to_store).await {
+                report.hash_store_error = Some(why);
+            }
+        }
+
+        report
+    }
+
+    async fn create_global_command(
+        &self,
+        ctx: &Context,
+        cmd: &SlashCommandEntry,
+    ) -> RegistrationReport {
+        let mut report = RegistrationReport::default();
+        let target = CommandTarget::Global;
+
+        let global_cmd = match target.create_command(ctx, &cmd.create).await {
+            Ok(global_cmd) => global_cmd,
+            Err(why) => {
+                report.push(cmd.name, target, why);
+                return report;
+            }
+        };
+        self.command_registered(cmd.name, target, global_cmd.id)
+            .await;
+
+        // The command itself was created even if its permissions fail in
+        // some guilds, which are reported on their own.
+        if cmd.has_permissions() {
+            let (unresolved, failures) = target
+                .set_command_permissions(ctx, global_cmd.id, cmd)
+                .await;
+            report.unresolved_roles.extend(unresolved);
+            report.permission_failures.extend(failures);
+        }
+        report.push(
+            cmd.name,
+            target,
+            RegistrationOutcome::Success(global_cmd.id),
+        );
+
+        report
+    }
+
+    async fn command_registered(&self, name: &str, target: CommandTarget, id: CommandId) {
+        let mut command_ids = self.command_ids.lock().await;
+        command_ids.insert((name.to_string(), target), id);
+    }
+
+    async fn before(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+        path: &str,
+    ) -> bool {
+        for hook in &self.before_hooks {
+            if !hook(ctx, interaction, path).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn after(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+        path: &str,
+        result: &Result<()>,
+    ) {
+        for hook in &self.after_hooks {
+            hook(ctx, interaction, path, result).await;
+        }
+    }
+
+    async fn unknown_command(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+    ) -> std::result::Result<(), SlashCommandError> {
+        let name = &interaction.data.name;
+        let result = match self.fallback {
+            Some(fallback) => fallback(ctx, interaction).await.map_err(Into::into),
+            None => Err(SlashCommandError::UnhandledSlashCommand(name.clone())),
+        };
+
+        // Commands known here can still end up here, e.g. with an unknown
+        // subcommand, and must not be deleted.
+        if self.prune_unknown
+            && !self.keep_remote.contains(name)
+            && !self.is_known(name, interaction.guild_id).await
+        {
+            // The user already got an answer, and a failed deletion is tried
+            // again the next time the command is used.
+            let _ = self.prune_unknown_command(ctx, interaction).await;
+        }
+
+        result
+    }
+
+    async fn on_error(
+        &self,
+        ctx: &Context,
+        interaction: &ApplicationCommandInteraction,
+        error: &SlashCommandError,
+    ) {
+        match self.error_handler {
+            Some(handler) => handler(ctx, interaction, error).await,
+            None => {
+                let _ = respond_with_error(ctx, interaction, error).await;
+            }
+        }
+    }
+}
+
+pub(crate) fn has_permissions(permissions: &CreateApplicationCommandPermissionsData) -> bool {
+    permissions
+        .0
+        .get("permissions")
+        .and_then(|p| p.as_array())
+        .is_some_and(|p| !p.is_empty())
+}
+
 impl Default for DefaultSlashCommandHandler {
     fn default() -> Self {
         Self {
-            to_add: Some(Default::default()),
+            to_add: Default::default(),
             callbacks: Default::default(),
+            bulk_overwrite: false,
+            prune_stale: false,
+            keep_remote: Default::default(),
+            command_ids: Default::default(),
+            concurrency: 1,
+            guild_targets: None,
+            dev_guild: None,
+            hash_store: None,
+            force_registration: false,
+            registration_shard: 0,
+            registered: AtomicBool::new(false),
+            before_hooks: Vec::new(),
+            after_hooks: Vec::new(),
+            error_handler: None,
+            fallback: None,
+            prune_unknown: false,
+            cooldowns: Default::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cooldown::tests::FakeClock, Cooldown, CooldownBucket};
+
+    fn entry(name: &'static str, guilds: Option<&'static [u64]>) -> SlashCommandEntry {
+        SlashCommandEntry {
+            name,
+            guilds: guilds.map(|guilds| guilds.iter().copied().map(GuildId).collect()),
+            create: Default::default(),
+            permissions: Default::default(),
+            deferred_permissions: Default::default(),
+        }
+    }
+
+    #[test]
+    fn global_commands_overlap_with_every_command() {
+        let global = entry("ping", None);
+        assert!(global.overlaps(&entry("ping", None)));
+        assert!(global.overlaps(&entry("ping", Some(&[1]))));
+        assert!(entry("ping", Some(&[1])).overlaps(&global));
+    }
+
+    #[test]
+    fn guild_commands_overlap_when_they_share_a_guild() {
+        let a = entry("ping", Some(&[1, 2]));
+        assert!(a.overlaps(&entry("ping", Some(&[2, 3]))));
+        assert!(!a.overlaps(&entry("ping", Some(&[3, 4]))));
+        assert!(!a.overlaps(&entry("ping", Some(&[]))));
+    }
+
+    #[test]
+    fn rejects_the_same_name_in_overlapping_guilds() {
+        let existing = [entry("ping", Some(&[1, 2])), entry("ban", None)];
+
+        match check_duplicate(&existing, &entry("ping", Some(&[2]))) {
+            Err(SlashCommandError::DuplicateSlashCommand {
+                name,
+                existing,
+                new,
+            }) => {
+                assert_eq!(name, "ping");
+                assert_eq!(existing, "guilds [1, 2]");
+                assert_eq!(new, "guilds [2]");
+            }
+            other => panic!("expected a duplicate, got {:?}", other),
         }
+        assert!(check_duplicate(&existing, &entry("ban", Some(&[5]))).is_err());
+    }
+
+    #[test]
+    fn allows_the_same_name_in_separate_guilds() {
+        let existing = [entry("ping", Some(&[1, 2])), entry("ban", None)];
+        assert!(check_duplicate(&existing, &entry("ping", Some(&[3]))).is_ok());
+        assert!(check_duplicate(&existing, &entry("kick", None)).is_ok());
+    }
+
+    #[test]
+    fn targets_are_set_before_commands_are_added() {
+        let mut handler = DefaultSlashCommandHandler::default();
+        handler.dev_guild(Some(GuildId(1))).unwrap();
+        handler
+            .insert_entry(entry("ping", None), CommandCallbacks::default())
+            .unwrap();
+
+        assert_eq!(handler.to_add.get_mut()[0].guilds, Some(vec![GuildId(1)]));
+        assert!(matches!(
+            handler.dev_guild(None),
+            Err(SlashCommandError::TargetsAfterCommands(_))
+        ));
+        assert!(matches!(
+            handler.guild_targets(GuildTargets::from_fn(|_| None)),
+            Err(SlashCommandError::TargetsAfterCommands(_))
+        ));
+    }
+
+    #[test]
+    fn manifest_entries_are_added_all_or_nothing() {
+        let mut handler = DefaultSlashCommandHandler::default();
+        handler
+            .insert_entry(entry("ban", Some(&[1])), CommandCallbacks::default())
+            .unwrap();
+
+        let callback: SlashCommandCallback = crate::command_unavailable;
+        let callbacks = [("ping", callback), ("ban", callback)];
+        let entries = vec![entry("ping", None), entry("ban", Some(&[1, 2]))];
+        assert!(matches!(
+            handler.add_manifest_entries(entries, callbacks),
+            Err(SlashCommandError::DuplicateSlashCommand { .. })
+        ));
+
+        let entries = vec![entry("ping", None), entry("ping", Some(&[2]))];
+        assert!(matches!(
+            handler.add_manifest_entries(entries, [("ping", callback)]),
+            Err(SlashCommandError::DuplicateSlashCommand { .. })
+        ));
+
+        assert_eq!(handler.to_add.get_mut().len(), 1);
+        assert!(!handler.callbacks.get_mut().contains_key("ping"));
+    }
+
+    fn interaction(name: &str, user_id: u64) -> ApplicationCommandInteraction {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "application_id": "1",
+            "type": 2,
+            "data": { "id": "1", "name": name, "type": 1 },
+            "channel_id": "1",
+            "user": {
+                "id": user_id.to_string(),
+                "username": "user",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cooldowns_follow_the_injected_clock() {
+        let clock = FakeClock::new();
+        let mut handler = DefaultSlashCommandHandler::default();
+        handler.clock(clock.clone());
+        let callbacks = CommandCallbacks {
+            cooldown: Some(Cooldown::new(
+                CooldownBucket::User,
+                Duration::from_secs(10),
+                1,
+            )),
+            ..Default::default()
+        };
+        handler
+            .insert_entry(entry("ping", None), callbacks)
+            .unwrap();
+
+        let first = interaction("ping", 1);
+        assert_eq!(handler.cooldown_remaining(&first).await, None);
+        assert_eq!(
+            handler.cooldown_remaining(&first).await,
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            handler.cooldown_remaining(&interaction("ping", 2)).await,
+            None
+        );
+
+        clock.advance(4);
+        assert_eq!(
+            handler.cooldown_remaining(&first).await,
+            Some(Duration::from_secs(6))
+        );
+
+        clock.advance(6);
+        assert_eq!(handler.cooldown_remaining(&first).await, None);
+    }
+
+    #[test]
+    fn cooldown_message_rounds_up() {
+        assert_eq!(
+            cooldown_message(Duration::from_millis(300)),
+            "This command is on cooldown, try again in 1 second."
+        );
+        assert_eq!(
+            cooldown_message(Duration::from_millis(1200)),
+            "This command is on cooldown, try again in 2 seconds."
+        );
+        assert_eq!(
+            cooldown_message(Duration::from_secs(5)),
+            "This command is on cooldown, try again in 5 seconds."
+        );
     }
 }
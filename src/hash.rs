@@ -0,0 +1,481 @@
+use crate::{
+    bulk::group_by_target, manifest, CommandTarget, RegistrationReport, SlashCommandEntry,
+};
+use serde_json::{Map, Value};
+use serenity::{async_trait, model::id::GuildId};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+    path::PathBuf,
+};
+use tokio::{fs, sync::Mutex};
+
+const ALL: &str = "all";
+
+/// Remembers the hashes of the command definitions that were last registered,
+/// so that unchanged targets can be skipped on the next start or reconnect.
+#[async_trait]
+pub trait HashStore: Send + Sync {
+    async fn load(&self) -> HashMap<String, u64>;
+
+    async fn save(&self, hashes: &HashMap<String, u64>) -> io::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryHashStore(Mutex<HashMap<String, u64>>);
+
+#[async_trait]
+impl HashStore for MemoryHashStore {
+    async fn load(&self) -> HashMap<String, u64> {
+        self.0.lock().await.clone()
+    }
+
+    async fn save(&self, hashes: &HashMap<String, u64>) -> io::Result<()> {
+        *self.0.lock().await = hashes.clone();
+        Ok(())
+    }
+}
+
+/// Stores the hashes as a JSON object. A missing or unreadable file counts as
+/// empty, so every target is registered again.
+#[derive(Debug, Clone)]
+pub struct FileHashStore {
+    path: PathBuf,
+}
+
+impl FileHashStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".tmp");
+        path.into()
+    }
+}
+
+#[async_trait]
+impl HashStore for FileHashStore {
+    async fn load(&self) -> HashMap<String, u64> {
+        let value: Value = match fs::read_to_string(&self.path)
+            .await
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        {
+            Some(value) => value,
+            None => return HashMap::new(),
+        };
+
+        value
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, hash)| {
+                let hash = u64::from_str_radix(hash.as_str()?, 16).ok()?;
+                Some((key.clone(), hash))
+            })
+            .collect()
+    }
+
+    async fn save(&self, hashes: &HashMap<String, u64>) -> io::Result<()> {
+        let object: Map<_, _> = hashes
+            .iter()
+            .map(|(key, hash)| (key.clone(), Value::String(format!("{:016x}", hash))))
+            .collect();
+        let contents = serde_json::to_string_pretty(&Value::Object(object))?;
+
+        // Written to a temporary file first, so that a crash while writing
+        // does not leave a truncated file behind.
+        let temp = self.temp_path();
+        fs::write(&temp, contents).await?;
+        fs::rename(&temp, &self.path).await
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust
+/// versions and processes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+pub(crate) struct CommandHashes {
+    all: u64,
+    targets: BTreeMap<CommandTarget, u64>,
+}
+
+impl CommandHashes {
+    pub(crate) fn new(entries: &[SlashCommandEntry]) -> Self {
+        let targets = group_by_target(entries)
+            .into_iter()
+            .map(|(target, entries)| {
+                // Only hash what this target sees, so that adding a guild to a
+                // command does not change the hash of its other guilds.
+                let guilds = match target {
+                    CommandTarget::Global => None,
+                    CommandTarget::Guild(guild_id) => Some(vec![guild_id]),
+                };
+                let mut definitions: Vec<_> = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let entry = SlashCommandEntry {
+                            guilds: guilds.clone(),
+                            ..entry.clone()
+                        };
+                        manifest::entry_manifest(&entry).to_string()
+                    })
+                    .collect();
+                definitions.sort();
+
+                (target, fnv1a(definitions.join("\n").as_bytes()))
+            })
+            .collect();
+
+        Self {
+            all: fnv1a(manifest::manifest_json(entries).as_bytes()),
+            targets,
+        }
+    }
+
+    pub(crate) fn is_unchanged(&self, stored: &HashMap<String, u64>) -> bool {
+        stored.get(ALL) == Some(&self.all)
+    }
+
+    pub(crate) fn changed_targets(&self, stored: &HashMap<String, u64>) -> BTreeSet<CommandTarget> {
+        self.targets
+            .iter()
+            .filter(|(target, hash)| stored.get(&target.to_string()) != Some(hash))
+            .map(|(&target, _)| target)
+            .collect()
+    }
+
+    /// Targets that had commands when they were last registered, but no
+    /// longer have any.
+    pub(crate) fn removed_targets(&self, stored: &HashMap<String, u64>) -> BTreeSet<CommandTarget> {
+        stored
+            .keys()
+            .filter_map(|key| parse_target(key))
+            .filter(|target| !self.targets.contains_key(target))
+            .collect()
+    }
+
+    pub(crate) fn targets(&self) -> impl Iterator<Item = CommandTarget> + '_ {
+        self.targets.keys().copied()
+    }
+
+    /// The hashes to store after registration. Targets with a failed
+    /// registration are left out so that they are retried, and so are the
+    /// `removed` targets unless clearing them failed.
+    pub(crate) fn to_store(
+        &self,
+        report: &RegistrationReport,
+        stored: &HashMap<String, u64>,
+        removed: &BTreeSet<CommandTarget>,
+    ) -> HashMap<String, u64> {
+        let failed: BTreeSet<_> = report
+            .failures()
+            .map(|r| r.target)
+            .chain(report.permission_failures.iter().map(|f| f.target))
+            .collect();
+
+        let mut hashes: HashMap<_, _> = self
+            .targets
+            .iter()
+            .filter(|(target, _)| !failed.contains(target))
+            .map(|(target, &hash)| (target.to_string(), hash))
+            .collect();
+        if report.prune_error.is_some() {
+            for target in removed {
+                let key = target.to_string();
+                if let Some(&hash) = stored.get(&key) {
+                    hashes.insert(key, hash);
+                }
+            }
+        } else if failed.is_empty() {
+            hashes.insert(ALL.to_string(), self.all);
+        }
+
+        hashes
+    }
+}
+
+/// What the hash store says about a set of entries: the targets that changed
+/// since they were last registered, and the ones that lost all commands.
+pub(crate) struct HashState {
+    pub(crate) hashes: CommandHashes,
+    pub(crate) stored: HashMap<String, u64>,
+    pub(crate) changed: BTreeSet<CommandTarget>,
+    pub(crate) removed: BTreeSet<CommandTarget>,
+}
+
+impl HashState {
+    /// With `force`, the stored hashes are ignored and every target counts as
+    /// changed. With `keep_global`, the global target is never removed, e.g.
+    /// while its commands are registered in a dev guild instead.
+    pub(crate) async fn load(
+        store: &dyn HashStore,
+        entries: &[SlashCommandEntry],
+        force: bool,
+        keep_global: bool,
+    ) -> Self {
+        let hashes = CommandHashes::new(entries);
+        let stored = if force {
+            HashMap::new()
+        } else {
+            store.load().await
+        };
+
+        let changed = hashes.changed_targets(&stored);
+        let mut removed = hashes.removed_targets(&stored);
+        if keep_global {
+            removed.remove(&CommandTarget::Global);
+        }
+
+        Self {
+            hashes,
+            stored,
+            changed,
+            removed,
+        }
+    }
+
+    pub(crate) fn is_unchanged(&self) -> bool {
+        self.hashes.is_unchanged(&self.stored)
+    }
+
+    /// The targets that registration skips.
+    pub(crate) fn skipped(&self) -> Vec<CommandTarget> {
+        let unchanged = self.is_unchanged();
+        self.hashes
+            .targets()
+            .filter(|target| unchanged || !self.changed.contains(target))
+            .collect()
+    }
+}
+
+/// The inverse of the `Display` implementation of [`CommandTarget`], which
+/// the stored hashes are keyed by.
+fn parse_target(key: &str) -> Option<CommandTarget> {
+    match key {
+        "global" => Some(CommandTarget::Global),
+        _ => key
+            .strip_prefix("guild ")?
+            .parse()
+            .ok()
+            .map(|guild_id| CommandTarget::Guild(GuildId(guild_id))),
+    }
+}
+
+/// Narrows the entries down to the given targets.
+pub(crate) fn restrict(
+    entries: &[SlashCommandEntry],
+    targets: &BTreeSet<CommandTarget>,
+) -> Vec<SlashCommandEntry> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.guilds {
+            None => targets
+                .contains(&CommandTarget::Global)
+                .then(|| entry.clone()),
+            Some(guilds) => {
+                let guilds: Vec<_> = guilds
+                    .iter()
+                    .copied()
+                    .filter(|&guild_id| targets.contains(&CommandTarget::Guild(guild_id)))
+                    .collect();
+                (!guilds.is_empty()).then(|| SlashCommandEntry {
+                    guilds: Some(guilds),
+                    ..entry.clone()
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PermissionFailure, RegistrationOutcome};
+    use serenity::builder::CreateApplicationCommand;
+    use std::{env, process, sync::Arc};
+
+    fn entry(name: &'static str, description: &str, guilds: Option<&[u64]>) -> SlashCommandEntry {
+        let mut create = CreateApplicationCommand::default();
+        create.name(name).description(description);
+
+        SlashCommandEntry {
+            name,
+            guilds: guilds.map(|guilds| guilds.iter().copied().map(GuildId).collect()),
+            create,
+            permissions: Default::default(),
+            deferred_permissions: Default::default(),
+        }
+    }
+
+    fn guild(guild_id: u64) -> CommandTarget {
+        CommandTarget::Guild(GuildId(guild_id))
+    }
+
+    fn stored(entries: &[SlashCommandEntry]) -> HashMap<String, u64> {
+        CommandHashes::new(entries).to_store(
+            &RegistrationReport::default(),
+            &HashMap::new(),
+            &BTreeSet::new(),
+        )
+    }
+
+    #[test]
+    fn unchanged_entries_hash_the_same() {
+        let entries = [entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))];
+        let hashes = CommandHashes::new(&entries);
+        let stored = stored(&entries);
+
+        assert!(hashes.is_unchanged(&stored));
+        assert!(hashes.changed_targets(&stored).is_empty());
+        assert!(hashes.removed_targets(&stored).is_empty());
+    }
+
+    #[test]
+    fn only_targets_that_see_a_change_are_changed() {
+        let stored = stored(&[entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))]);
+
+        let hashes = CommandHashes::new(&[
+            entry("ping", "Ping", None),
+            entry("ban", "Ban", Some(&[1, 2])),
+        ]);
+        assert!(!hashes.is_unchanged(&stored));
+        assert_eq!(hashes.changed_targets(&stored), BTreeSet::from([guild(2)]));
+
+        let hashes =
+            CommandHashes::new(&[entry("ping", "Pong", None), entry("ban", "Ban", Some(&[1]))]);
+        assert_eq!(
+            hashes.changed_targets(&stored),
+            BTreeSet::from([CommandTarget::Global])
+        );
+    }
+
+    #[test]
+    fn targets_without_commands_are_removed() {
+        let mut stored = stored(&[entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))]);
+        stored.insert("not a target".to_string(), 0);
+
+        let hashes = CommandHashes::new(&[entry("ban", "Ban", Some(&[2]))]);
+        assert_eq!(
+            hashes.removed_targets(&stored),
+            BTreeSet::from([CommandTarget::Global, guild(1)])
+        );
+        assert_eq!(hashes.changed_targets(&stored), BTreeSet::from([guild(2)]));
+
+        let hashes = CommandHashes::new(&[]);
+        assert_eq!(
+            hashes.removed_targets(&stored),
+            BTreeSet::from([CommandTarget::Global, guild(1)])
+        );
+        assert!(!hashes.is_unchanged(&stored));
+    }
+
+    #[test]
+    fn failed_targets_are_not_stored() {
+        let entries = [
+            entry("ping", "Ping", None),
+            entry("ban", "Ban", Some(&[1, 2])),
+        ];
+        let hashes = CommandHashes::new(&entries);
+
+        let mut report = RegistrationReport::default();
+        report.push(
+            "ban",
+            guild(2),
+            RegistrationOutcome::ValidationError("rejected".to_string()),
+        );
+        let to_store = hashes.to_store(&report, &HashMap::new(), &BTreeSet::new());
+
+        let mut keys: Vec<_> = to_store.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["global", "guild 1"]);
+    }
+
+    #[test]
+    fn targets_with_failed_permissions_are_not_stored() {
+        let hashes = CommandHashes::new(&[entry("ping", "Ping", None)]);
+
+        let mut report = RegistrationReport::default();
+        report.permission_failures.push(PermissionFailure {
+            command: "ping".to_string(),
+            target: CommandTarget::Global,
+            guild_id: GuildId(1),
+            error: Arc::new(serenity::Error::Other("missing access")),
+        });
+        let to_store = hashes.to_store(&report, &HashMap::new(), &BTreeSet::new());
+
+        assert!(to_store.is_empty());
+    }
+
+    #[test]
+    fn removed_targets_are_kept_until_cleared() {
+        let stored = stored(&[entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))]);
+        let hashes = CommandHashes::new(&[entry("ban", "Ban", Some(&[1]))]);
+        let removed = hashes.removed_targets(&stored);
+
+        let report = RegistrationReport::default();
+        let to_store = hashes.to_store(&report, &stored, &removed);
+        assert!(!to_store.contains_key("global"));
+        assert!(hashes.is_unchanged(&to_store));
+
+        let report = RegistrationReport {
+            prune_error: Some(serenity::Error::Other("clearing failed")),
+            ..Default::default()
+        };
+        let to_store = hashes.to_store(&report, &stored, &removed);
+        assert_eq!(to_store.get("global"), stored.get("global"));
+        assert!(!hashes.is_unchanged(&to_store));
+        assert_eq!(hashes.removed_targets(&to_store), removed);
+    }
+
+    #[test]
+    fn keys_parse_back_into_targets() {
+        for target in [CommandTarget::Global, guild(81384788765712384)] {
+            assert_eq!(parse_target(&target.to_string()), Some(target));
+        }
+        assert_eq!(parse_target("all"), None);
+        assert_eq!(parse_target("guild x"), None);
+    }
+
+    #[test]
+    fn restrict_keeps_only_the_given_targets() {
+        let entries = [
+            entry("ping", "Ping", None),
+            entry("ban", "Ban", Some(&[1, 2, 3])),
+            entry("kick", "Kick", Some(&[3])),
+        ];
+
+        let restricted = restrict(&entries, &BTreeSet::from([guild(1), guild(2)]));
+        assert_eq!(restricted.len(), 1);
+        assert_eq!(restricted[0].name, "ban");
+        assert_eq!(restricted[0].guilds, Some(vec![GuildId(1), GuildId(2)]));
+
+        let restricted = restrict(&entries, &BTreeSet::from([CommandTarget::Global]));
+        assert_eq!(restricted.len(), 1);
+        assert_eq!(restricted[0].name, "ping");
+        assert_eq!(restricted[0].guilds, None);
+    }
+
+    #[tokio::test]
+    async fn file_store_round_trips() {
+        let path = env::temp_dir().join(format!("slash-command-hashes-{}.json", process::id()));
+        let store = FileHashStore::new(&path);
+        assert!(store.load().await.is_empty());
+
+        let hashes = HashMap::from([("global".to_string(), u64::MAX), (ALL.to_string(), 1)]);
+        store.save(&hashes).await.unwrap();
+        assert_eq!(store.load().await, hashes);
+        assert!(!store.temp_path().exists());
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(store.load().await.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,14 +1,44 @@
 mod builder;
+mod bulk;
+mod cooldown;
 mod error;
 mod ext;
+mod guilds;
 mod handler;
+mod hash;
 #[macro_use]
 mod macros;
+mod manifest;
+mod permissions;
+mod plan;
+mod report;
+mod subcommand;
+mod sync;
+mod target;
+mod validation;
 
 pub use builder::SlashCommandBuilder;
-pub use error::SlashCommandError;
+pub use cooldown::{Clock, Cooldown, CooldownBucket, SystemClock};
+pub use error::{command_unavailable, respond_with_error, SlashCommandError};
 pub use ext::ApplicationCommandInteractionExt;
-pub use handler::{DefaultSlashCommandHandler, SlashCommandEntry, SlashCommandHandler};
+pub use guilds::{GuildOverride, GuildTargets};
+pub use handler::{
+    DefaultSlashCommandHandler, SlashCommandEntry, SlashCommandHandler, DEV_GUILD_VAR,
+};
+pub use hash::{FileHashStore, HashStore, MemoryHashStore};
+#[cfg(feature = "toml")]
+pub use manifest::parse_manifest_toml;
+pub use manifest::{load_manifest, manifest, manifest_json, parse_manifest, parse_manifest_json};
+pub use permissions::{
+    DeferredPermission, DeferredPermissions, DeferredRole, PermissionsUpdate, RoleResolver,
+    UnresolvedRole,
+};
+pub use plan::{PlannedChange, RegistrationPlan, RemoteSnapshot};
+pub use report::{CommandRegistration, PermissionFailure, RegistrationOutcome, RegistrationReport};
+pub use subcommand::{subcommand_path, CommandCallbacks};
+pub use sync::{SyncSummary, SyncedCommand};
+pub use target::CommandTarget;
+pub use validation::{validate_entries, validate_entry, ValidationError};
 
 pub use proc_macro::slash_command;
 
@@ -17,7 +47,9 @@ use serenity::{
     builder::{CreateApplicationCommand, CreateApplicationCommandPermissionsData},
     client::Context,
     futures::future::BoxFuture,
-    model::interactions::application_command::ApplicationCommandInteraction,
+    model::interactions::application_command::{
+        ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    },
     Result,
 };
 
@@ -26,9 +58,46 @@ pub type SlashCommandCallback = for<'fut> fn(
     &'fut ApplicationCommandInteraction,
 ) -> BoxFuture<'fut, serenity::Result<()>>;
 
+/// Passes, or fails with a reason that is shown to the user.
+pub type CheckResult = std::result::Result<(), String>;
+
+/// A condition that has to pass before a command runs.
+pub type Check = for<'fut> fn(
+    &'fut Context,
+    &'fut ApplicationCommandInteraction,
+) -> BoxFuture<'fut, CheckResult>;
+
+pub type BeforeHook = for<'fut> fn(
+    &'fut Context,
+    &'fut ApplicationCommandInteraction,
+    &'fut str,
+) -> BoxFuture<'fut, bool>;
+
+pub type AfterHook = for<'fut> fn(
+    &'fut Context,
+    &'fut ApplicationCommandInteraction,
+    &'fut str,
+    &'fut serenity::Result<()>,
+) -> BoxFuture<'fut, ()>;
+
+pub type ErrorHandler = for<'fut> fn(
+    &'fut Context,
+    &'fut ApplicationCommandInteraction,
+    &'fut SlashCommandError,
+) -> BoxFuture<'fut, ()>;
+
+/// The callback of a subcommand, which only receives the options of that
+/// subcommand.
+pub type SubcommandCallback = for<'fut> fn(
+    &'fut Context,
+    &'fut ApplicationCommandInteraction,
+    &'fut [ApplicationCommandInteractionDataOption],
+) -> BoxFuture<'fut, serenity::Result<()>>;
+
 #[async_trait]
 pub trait SlashCommand: Send {
     const NAME: &'static str;
+    /// The default guilds, which [`GuildTargets`] can override at runtime.
     const GUILDS: Option<&'static [u64]>;
 
     fn create(c: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand;
@@ -39,23 +108,45 @@ pub trait SlashCommand: Send {
         p
     }
 
+    fn deferred_permissions(p: &mut DeferredPermissions) -> &mut DeferredPermissions {
+        p
+    }
+
+    fn checks() -> Vec<Check> {
+        Vec::new()
+    }
+
+    fn cooldown() -> Option<Cooldown> {
+        None
+    }
+
     async fn callback(
         ctx: &'async_trait Context,
         interaction: &'async_trait ApplicationCommandInteraction,
     ) -> Result<()>;
 
-    async fn register<H: SlashCommandHandler>(handler: &mut H) {
-        handler
-            .create_slash_command(|cmd| {
-                cmd.name(Self::NAME)
-                    .callback(Self::callback)
-                    .create_application_command(|c| Self::create(c).name(Self::NAME))
-                    .create_permissions(Self::permissions);
-                if let Some(guilds) = Self::GUILDS {
-                    cmd.guilds(guilds);
-                }
-                cmd
-            })
-            .await;
+    fn build(cmd: &mut SlashCommandBuilder) -> &mut SlashCommandBuilder {
+        cmd.name(Self::NAME)
+            .callback(Self::callback)
+            .create_application_command(|c| Self::create(c).name(Self::NAME))
+            .create_permissions(Self::permissions)
+            .create_deferred_permissions(Self::deferred_permissions);
+        for check in Self::checks() {
+            cmd.check(check);
+        }
+        if let Some(cooldown) = Self::cooldown() {
+            cmd.cooldown(cooldown);
+        }
+        if let Some(guilds) = Self::GUILDS {
+            cmd.guilds(guilds.iter().copied());
+        }
+        cmd
+    }
+
+    async fn register<H: SlashCommandHandler>(
+        handler: &mut H,
+    ) -> std::result::Result<(), SlashCommandError> {
+        handler.create_slash_command(Self::build).await?;
+        Ok(())
     }
 }
@@ -6,6 +6,12 @@ macro_rules! slash_command_permissions {
     ($vis:vis $name:ident: $allow:literal for Role($id:literal)) => {
         slash_command_permissions!(@INNER $vis $name: Role ($id): $allow);
     };
+    ($vis:vis $name:ident: $allow:literal for RoleName($role:literal)) => {
+        slash_command_permissions!(@DEFERRED $vis $name: |d| d.role_name($role, $allow));
+    };
+    ($vis:vis $name:ident: $allow:literal for Resolver($resolver:path)) => {
+        slash_command_permissions!(@DEFERRED $vis $name: |d| d.role_resolver($resolver, $allow));
+    };
     (@INNER $vis:vis $name:ident: $variant:ident ($id:literal): $allow:literal) => {
         #[allow(non_camel_case_types)]
         $vis struct $name;
@@ -18,6 +24,24 @@ macro_rules! slash_command_permissions {
                         .permission($allow)
                 })
             }
+
+            pub fn apply_deferred(d: &mut $crate::DeferredPermissions) -> &mut $crate::DeferredPermissions {
+                d
+            }
+        }
+    };
+    (@DEFERRED $vis:vis $name:ident: |$d:ident| $body:expr) => {
+        #[allow(non_camel_case_types)]
+        $vis struct $name;
+
+        impl $name {
+            pub fn apply(p: &mut ::serenity::builder::CreateApplicationCommandPermissionsData) -> &mut ::serenity::builder::CreateApplicationCommandPermissionsData {
+                p
+            }
+
+            pub fn apply_deferred($d: &mut $crate::DeferredPermissions) -> &mut $crate::DeferredPermissions {
+                $body
+            }
         }
     };
 }
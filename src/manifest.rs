@@ -0,0 +1,301 @@
+use crate::{
+    DeferredPermissions, DeferredRole, GuildOverride, SlashCommandEntry, SlashCommandError,
+};
+use serde_json::{json, Map, Value};
+use serenity::builder::{CreateApplicationCommand, CreateApplicationCommandPermissionsData};
+use std::{fs, path::Path};
+
+/// Serializes command definitions to a JSON value that does not depend on
+/// registration order, so that changes to it can be reviewed as diffs.
+pub fn manifest(entries: &[SlashCommandEntry]) -> Value {
+    let mut commands: Vec<_> = entries.iter().map(entry_manifest).collect();
+    commands.sort_by_key(|command| command.to_string());
+
+    json!({ "commands": commands })
+}
+
+pub fn manifest_json(entries: &[SlashCommandEntry]) -> String {
+    serde_json::to_string_pretty(&manifest(entries)).expect("JSON values always serialize")
+}
+
+/// Loads command definitions in the format written by [`manifest`]. Files
+/// ending in `.toml` are read as TOML when the `toml` feature is enabled,
+/// anything else as JSON.
+pub fn load_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<SlashCommandEntry>, SlashCommandError> {
+    let path = path.as_ref();
+    let contents =
+        fs::read_to_string(path).map_err(|why| invalid(format!("{}: {}", path.display(), why)))?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "toml")]
+        Some("toml") => parse_manifest_toml(&contents),
+        _ => parse_manifest_json(&contents),
+    }
+}
+
+pub fn parse_manifest_json(contents: &str) -> Result<Vec<SlashCommandEntry>, SlashCommandError> {
+    let value = serde_json::from_str(contents).map_err(|why| invalid(why.to_string()))?;
+    parse_manifest(&value)
+}
+
+#[cfg(feature = "toml")]
+pub fn parse_manifest_toml(contents: &str) -> Result<Vec<SlashCommandEntry>, SlashCommandError> {
+    let value: toml::Value = toml::from_str(contents).map_err(|why| invalid(why.to_string()))?;
+    let value = serde_json::to_value(value).map_err(|why| invalid(why.to_string()))?;
+    parse_manifest(&value)
+}
+
+pub fn parse_manifest(value: &Value) -> Result<Vec<SlashCommandEntry>, SlashCommandError> {
+    value
+        .get("commands")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("expected a \"commands\" list".to_string()))?
+        .iter()
+        .map(parse_entry)
+        .collect()
+}
+
+fn invalid(reason: String) -> SlashCommandError {
+    SlashCommandError::InvalidManifest(reason)
+}
+
+/// Builds a command from its JSON definition, failing with the first field
+/// that a command does not have.
+pub(crate) fn command_from_json(
+    command: &Map<String, Value>,
+) -> Result<CreateApplicationCommand, String> {
+    let mut create = CreateApplicationCommand::default();
+    for (key, value) in command {
+        let key = match key.as_str() {
+            "type" => "type",
+            "name" => "name",
+            "description" => "description",
+            "options" => "options",
+            "default_permission" => "default_permission",
+            _ => return Err(key.clone()),
+        };
+        create.0.insert(key, value.clone());
+    }
+    Ok(create)
+}
+
+fn parse_entry(value: &Value) -> Result<SlashCommandEntry, SlashCommandError> {
+    let name = value
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("every command needs a name".to_string()))?;
+    let field = |field: &str, reason: &str| invalid(format!("{}: {} {}", name, field, reason));
+
+    let guilds = match value.get("guilds") {
+        None | Some(Value::Null) => None,
+        Some(guilds) => GuildOverride::from_json(guilds)
+            .map_err(|reason| field("guilds", &reason))?
+            .into_guilds(),
+    };
+
+    let mut create = match value.get("command") {
+        None => CreateApplicationCommand::default(),
+        Some(command) => {
+            let command = command
+                .as_object()
+                .ok_or_else(|| field("command", "must be an object"))?;
+            command_from_json(command)
+                .map_err(|key| field("command", &format!("has unknown field '{}'", key)))?
+        }
+    };
+    create.name(name);
+
+    let mut permissions = CreateApplicationCommandPermissionsData::default();
+    if let Some(value) = value.get("permissions") {
+        if !value.is_array() {
+            return Err(field("permissions", "must be a list"));
+        }
+        permissions.0.insert("permissions", value.clone());
+    }
+
+    let mut deferred_permissions = DeferredPermissions::default();
+    for p in value
+        .get("deferred_permissions")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let role_name = p
+            .get("role_name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| field("deferred_permissions", "only support role names"))?;
+        let permission = p
+            .get("permission")
+            .and_then(Value::as_bool)
+            .ok_or_else(|| field("deferred_permissions", "need a boolean permission"))?;
+        deferred_permissions.role_name(role_name, permission);
+    }
+
+    Ok(SlashCommandEntry {
+        // Command names are `&'static str` everywhere else, and a manifest is
+        // only loaded once at startup.
+        name: Box::leak(name.to_string().into_boxed_str()),
+        guilds,
+        create,
+        permissions,
+        deferred_permissions,
+    })
+}
+
+pub(crate) fn entry_manifest(entry: &SlashCommandEntry) -> Value {
+    let guilds = entry.guilds.as_ref().map(|guilds| {
+        let mut guilds: Vec<_> = guilds.iter().map(|guild_id| guild_id.0).collect();
+        guilds.sort_unstable();
+        guilds.dedup();
+        guilds.iter().map(u64::to_string).collect::<Vec<_>>()
+    });
+
+    let deferred_permissions: Vec<_> = entry
+        .deferred_permissions
+        .0
+        .iter()
+        .map(|p| match &p.role {
+            DeferredRole::Name(name) => json!({ "role_name": name, "permission": p.permission }),
+            DeferredRole::Resolver(_) => {
+                json!({ "role_resolver": true, "permission": p.permission })
+            }
+        })
+        .collect();
+
+    let permissions = entry
+        .permissions
+        .0
+        .get("permissions")
+        .cloned()
+        .unwrap_or_else(|| json!([]));
+
+    sorted(json!({
+        "name": entry.name,
+        "guilds": guilds,
+        "command": entry.create.0,
+        "permissions": permissions,
+        "deferred_permissions": deferred_permissions,
+    }))
+}
+
+/// Sorts object keys recursively, regardless of whether serde_json keeps
+/// insertion order.
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sorted).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::{builder::CreateApplicationCommand, model::id::GuildId};
+
+    fn entry(name: &'static str, guilds: Option<&[u64]>) -> SlashCommandEntry {
+        let mut create = CreateApplicationCommand::default();
+        create.name(name).description("A command");
+
+        SlashCommandEntry {
+            name,
+            guilds: guilds.map(|guilds| guilds.iter().copied().map(GuildId).collect()),
+            create,
+            permissions: Default::default(),
+            deferred_permissions: Default::default(),
+        }
+    }
+
+    #[test]
+    fn sorts_keys_recursively() {
+        let value = sorted(json!({
+            "b": { "z": 1, "a": [{ "y": 2, "x": 3 }] },
+            "a": null,
+        }));
+        assert_eq!(
+            value.to_string(),
+            r#"{"a":null,"b":{"a":[{"x":3,"y":2}],"z":1}}"#
+        );
+    }
+
+    #[test]
+    fn does_not_depend_on_registration_order() {
+        let a = entry("ban", Some(&[2, 1, 2]));
+        let b = entry("ping", None);
+        assert_eq!(manifest(&[a.clone(), b.clone()]), manifest(&[b, a]));
+    }
+
+    #[test]
+    fn lists_guilds_sorted_and_deduplicated() {
+        let value = manifest(&[entry("ban", Some(&[2, 1, 2])), entry("ping", None)]);
+        let commands = value["commands"].as_array().unwrap();
+
+        assert_eq!(commands[0]["name"], "ban");
+        assert_eq!(commands[0]["guilds"], json!(["1", "2"]));
+        assert_eq!(commands[1]["name"], "ping");
+        assert_eq!(commands[1]["guilds"], Value::Null);
+        assert_eq!(commands[1]["command"]["description"], "A command");
+        assert_eq!(commands[1]["permissions"], json!([]));
+    }
+
+    #[test]
+    fn parses_commands() {
+        let entries = parse_manifest(&json!({
+            "commands": [
+                {
+                    "name": "ban",
+                    "guilds": ["1"],
+                    "command": { "description": "Bans a user", "default_permission": false },
+                    "permissions": [{ "id": "2", "type": 1, "permission": true }],
+                    "deferred_permissions": [{ "role_name": "Mods", "permission": true }],
+                },
+                { "name": "ping", "command": { "description": "Ping" } },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "ban");
+        assert_eq!(entries[0].guilds, Some(vec![GuildId(1)]));
+        assert_eq!(entries[0].create.0["name"], "ban");
+        assert_eq!(entries[0].create.0["default_permission"], false);
+        assert!(entries[0].has_permissions());
+        assert_eq!(entries[1].name, "ping");
+        assert_eq!(entries[1].guilds, None);
+    }
+
+    #[test]
+    fn rejects_unknown_command_fields() {
+        let error = parse_manifest_json(
+            r#"{ "commands": [{ "name": "ping", "command": { "descripton": "Ping" } }] }"#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid command manifest: ping: command has unknown field 'descripton'"
+        );
+    }
+
+    #[test]
+    fn loads_what_it_exports() {
+        let mut ban = entry("ban", Some(&[2, 1]));
+        ban.create.default_permission(false);
+        ban.deferred_permissions.role_name("Mods", true);
+        let entries = [ban, entry("ping", None)];
+
+        let exported = manifest_json(&entries);
+        let loaded = parse_manifest_json(&exported).unwrap();
+
+        assert_eq!(manifest_json(&loaded), exported);
+    }
+}
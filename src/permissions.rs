@@ -0,0 +1,192 @@
+use crate::SlashCommandEntry;
+use serenity::{
+    builder::{CreateApplicationCommandPermissionData, CreateApplicationCommandPermissionsData},
+    client::Context,
+    model::{
+        id::{CommandId, GuildId, RoleId},
+        interactions::application_command::ApplicationCommandPermissionType,
+    },
+    Result,
+};
+use std::{fmt, sync::Arc};
+
+pub type RoleResolver = Arc<dyn Fn(GuildId) -> Option<RoleId> + Send + Sync>;
+
+#[derive(Clone)]
+pub enum DeferredRole {
+    Name(String),
+    Resolver(RoleResolver),
+}
+
+impl fmt::Debug for DeferredRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeferredRole::Name(name) => f.debug_tuple("Name").field(name).finish(),
+            DeferredRole::Resolver(_) => f.debug_tuple("Resolver").finish(),
+        }
+    }
+}
+
+impl fmt::Display for DeferredRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeferredRole::Name(name) => write!(f, "role '{}'", name),
+            DeferredRole::Resolver(_) => f.write_str("resolved role"),
+        }
+    }
+}
+
+/// A permission whose role is only known once the guild it applies to is,
+/// since role IDs differ between guilds.
+#[derive(Debug, Clone)]
+pub struct DeferredPermission {
+    pub role: DeferredRole,
+    pub permission: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeferredPermissions(pub Vec<DeferredPermission>);
+
+impl DeferredPermissions {
+    pub fn role_name<S: Into<String>>(&mut self, name: S, permission: bool) -> &mut Self {
+        self.0.push(DeferredPermission {
+            role: DeferredRole::Name(name.into()),
+            permission,
+        });
+        self
+    }
+
+    pub fn role_resolver<F>(&mut self, resolver: F, permission: bool) -> &mut Self
+    where
+        F: Fn(GuildId) -> Option<RoleId> + Send + Sync + 'static,
+    {
+        self.0.push(DeferredPermission {
+            role: DeferredRole::Resolver(Arc::new(resolver)),
+            permission,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnresolvedRole {
+    pub command: String,
+    pub guild_id: GuildId,
+    pub role: DeferredRole,
+}
+
+impl fmt::Display for UnresolvedRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{}: {} not found in guild {}",
+            self.command, self.role, self.guild_id.0
+        )
+    }
+}
+
+/// The outcome of [`update_permissions`].
+///
+/// [`update_permissions`]: crate::DefaultSlashCommandHandler::update_permissions
+#[derive(Debug, Clone, Default)]
+pub struct PermissionsUpdate {
+    pub unresolved_roles: Vec<UnresolvedRole>,
+    /// Commands whose ID could not be found, usually because they have not
+    /// been registered in the guild yet. Their permissions were not updated.
+    pub missing_commands: Vec<String>,
+}
+
+impl fmt::Display for PermissionsUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("permissions updated")?;
+
+        for name in &self.missing_commands {
+            write!(f, "\n  /{} is not registered", name)?;
+        }
+
+        for unresolved in &self.unresolved_roles {
+            write!(f, "\n  {}", unresolved)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the permissions of a command for one guild, resolving role names
+/// against the guild's roles from the cache, or over HTTP if the guild is not
+/// cached.
+pub(crate) async fn resolve_permissions(
+    ctx: &Context,
+    cmd: &SlashCommandEntry,
+    guild_id: GuildId,
+) -> (CreateApplicationCommandPermissionsData, Vec<UnresolvedRole>) {
+    let mut permissions = cmd.permissions.clone();
+    let mut unresolved = Vec::new();
+
+    if cmd.deferred_permissions.is_empty() {
+        return (permissions, unresolved);
+    }
+
+    let roles = match ctx.cache.guild_roles(guild_id).await {
+        Some(roles) => Some(roles),
+        None => guild_id.roles(ctx).await.ok(),
+    };
+
+    for deferred in &cmd.deferred_permissions.0 {
+        let role_id = match &deferred.role {
+            DeferredRole::Name(name) => roles
+                .as_ref()
+                .and_then(|roles| roles.values().find(|role| &role.name == name))
+                .map(|role| role.id),
+            DeferredRole::Resolver(resolver) => resolver(guild_id),
+        };
+
+        match role_id {
+            Some(role_id) => {
+                let mut data = CreateApplicationCommandPermissionData::default();
+                data.kind(ApplicationCommandPermissionType::Role)
+                    .id(role_id.0)
+                    .permission(deferred.permission);
+                permissions.add_permission(data);
+            }
+            None => unresolved.push(UnresolvedRole {
+                command: cmd.name.to_string(),
+                guild_id,
+                role: deferred.role.clone(),
+            }),
+        }
+    }
+
+    (permissions, unresolved)
+}
+
+/// The permissions a guild currently has for the given commands. Setting the
+/// permissions of a guild in one batch replaces those of every command, so
+/// the ones of commands that are not managed locally have to be sent back.
+pub(crate) async fn remote_permissions(
+    ctx: &Context,
+    guild_id: GuildId,
+    commands: impl Fn(CommandId) -> bool,
+) -> Result<Vec<(CommandId, CreateApplicationCommandPermissionsData)>> {
+    let remote = guild_id
+        .get_application_commands_permissions(ctx)
+        .await?
+        .into_iter()
+        .filter(|command| commands(command.id))
+        .map(|command| {
+            let mut permissions = CreateApplicationCommandPermissionsData::default();
+            for p in command.permissions {
+                permissions.create_permission(|data| {
+                    data.kind(p.kind).id(p.id.0).permission(p.permission)
+                });
+            }
+            (command.id, permissions)
+        })
+        .collect();
+
+    Ok(remote)
+}
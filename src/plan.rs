@@ -0,0 +1,713 @@
+use crate::{
+    bulk::{group_by_target, kept_names},
+    sync::{local_definition, remote_definition, SyncSummary, SyncedCommand},
+    CommandTarget, SlashCommandEntry,
+};
+use serenity::{
+    client::Context,
+    model::{
+        id::{CommandId, GuildId},
+        interactions::application_command::{ApplicationCommand, ApplicationCommandPermission},
+    },
+    Result,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct RemoteSnapshot {
+    pub commands: HashMap<CommandTarget, Vec<ApplicationCommand>>,
+    pub permissions: HashMap<GuildId, Vec<ApplicationCommandPermission>>,
+    pub guilds: Vec<GuildId>,
+}
+
+impl RemoteSnapshot {
+    pub async fn fetch(ctx: &Context, entries: &[SlashCommandEntry]) -> Result<Self> {
+        Self::fetch_targets(ctx, entries, BTreeSet::new()).await
+    }
+
+    /// Also fetches the commands of `extra` targets, which have no local
+    /// commands but can still be changed, e.g. by pruning.
+    pub(crate) async fn fetch_targets(
+        ctx: &Context,
+        entries: &[SlashCommandEntry],
+        extra: BTreeSet<CommandTarget>,
+    ) -> Result<Self> {
+        let mut targets: BTreeSet<_> = group_by_target(entries).into_keys().collect();
+        targets.insert(CommandTarget::Global);
+        targets.extend(extra);
+
+        let guilds = ctx.cache.guilds().await;
+
+        let mut permission_guilds: BTreeSet<_> = targets
+            .iter()
+            .filter_map(|target| match target {
+                CommandTarget::Global => None,
+                CommandTarget::Guild(guild_id) => Some(*guild_id),
+            })
+            .collect();
+        if entries
+            .iter()
+            .any(|cmd| cmd.guilds.is_none() && cmd.has_permissions())
+        {
+            permission_guilds.extend(guilds.iter().copied());
+        }
+
+        let mut snapshot = Self {
+            guilds,
+            ..Default::default()
+        };
+
+        for target in targets {
+            snapshot
+                .commands
+                .insert(target, target.get_commands(ctx).await?);
+        }
+
+        for guild_id in permission_guilds {
+            snapshot.permissions.insert(
+                guild_id,
+                guild_id.get_application_commands_permissions(ctx).await?,
+            );
+        }
+
+        Ok(snapshot)
+    }
+
+    fn command(&self, target: CommandTarget, name: &str) -> Option<&ApplicationCommand> {
+        self.commands.get(&target)?.iter().find(|c| c.name == name)
+    }
+
+    fn permissions_match(
+        &self,
+        guild_id: GuildId,
+        command_id: CommandId,
+        cmd: &SlashCommandEntry,
+    ) -> bool {
+        // Deferred permissions can only be compared once they are resolved,
+        // which needs the guild's roles.
+        if !cmd.deferred_permissions.is_empty() {
+            return false;
+        }
+
+        let remote = self
+            .permissions
+            .get(&guild_id)
+            .and_then(|p| p.iter().find(|p| p.id == command_id));
+
+        let remote: BTreeSet<_> = match remote {
+            Some(remote) => remote
+                .permissions
+                .iter()
+                .map(|p| (p.id.0, p.kind as u64, p.permission))
+                .collect(),
+            None => return false,
+        };
+
+        let local: BTreeSet<_> = cmd
+            .permissions
+            .0
+            .get("permissions")
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|p| {
+                let id = match p.get("id")? {
+                    serde_json::Value::String(s) => s.parse().ok()?,
+                    v => v.as_u64()?,
+                };
+                Some((
+                    id,
+                    p.get("type")?.as_u64()?,
+                    p.get("permission")?.as_bool()?,
+                ))
+            })
+            .collect();
+
+        remote == local
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedChange {
+    Create {
+        name: String,
+    },
+    Update {
+        name: String,
+        command_id: CommandId,
+    },
+    Delete {
+        name: String,
+        command_id: CommandId,
+    },
+    SetPermissions {
+        name: String,
+        global: bool,
+    },
+    /// Replaces every command of the target with these ones in a single
+    /// request, which also deletes the ones that are planned to be deleted.
+    Overwrite {
+        names: Vec<String>,
+    },
+}
+
+impl fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedChange::Create { name } => write!(f, "create /{}", name),
+            PlannedChange::Update { name, command_id } => {
+                write!(f, "update /{} ({})", name, command_id.0)
+            }
+            PlannedChange::Delete { name, command_id } => {
+                write!(f, "delete /{} ({})", name, command_id.0)
+            }
+            PlannedChange::SetPermissions {
+                name,
+                global: false,
+            } => {
+                write!(f, "set permissions of /{}", name)
+            }
+            PlannedChange::SetPermissions { name, global: true } => {
+                write!(f, "set permissions of global /{}", name)
+            }
+            PlannedChange::Overwrite { names } => {
+                write!(f, "overwrite with /{}", names.join(", /"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegistrationPlan {
+    pub changes: BTreeMap<CommandTarget, Vec<PlannedChange>>,
+    pub unchanged: Vec<SyncedCommand>,
+    /// Targets whose commands did not change since they were last registered.
+    pub skipped: Vec<CommandTarget>,
+}
+
+/// The settings of the handler that decide what registration does.
+pub(crate) struct RegistrationMode<'a> {
+    pub(crate) bulk_overwrite: bool,
+    /// The full command set that stale commands are pruned against, if
+    /// pruning is enabled.
+    pub(crate) prune: Option<&'a [SlashCommandEntry]>,
+    pub(crate) keep: &'a HashSet<String>,
+    /// Entries that are not sent because they are invalid. A bulk overwrite
+    /// keeps their remote copies.
+    pub(crate) invalid: &'a [SlashCommandEntry],
+    /// Targets without local commands that a bulk overwrite clears.
+    pub(crate) removed: &'a BTreeSet<CommandTarget>,
+}
+
+impl RegistrationPlan {
+    /// Computes the changes that synchronization makes to bring the remote
+    /// state in line with `entries`. Without a snapshot every command is
+    /// assumed to be missing.
+    pub fn new(
+        entries: &[SlashCommandEntry],
+        remote: Option<&RemoteSnapshot>,
+        keep: &HashSet<String>,
+    ) -> Self {
+        let mut plan = Self::default();
+
+        let mut groups = group_by_target(entries);
+        if remote.is_some() {
+            groups.entry(CommandTarget::Global).or_default();
+        }
+
+        for (target, cmds) in groups {
+            for cmd in &cmds {
+                let command_id = plan.plan_command(target, cmd, remote);
+                plan.plan_permissions(target, cmd, remote, command_id);
+            }
+
+            let local: HashSet<_> = cmds.iter().map(|cmd| cmd.name).collect();
+            plan.plan_stale(target, remote, |name| {
+                local.contains(name) || keep.contains(name)
+            });
+        }
+
+        plan
+    }
+
+    /// Computes the changes that registration makes with the given mode. The
+    /// `entries` are the ones that are sent, without the invalid ones and the
+    /// targets the hash store skips.
+    pub(crate) fn registration(
+        entries: &[SlashCommandEntry],
+        remote: Option<&RemoteSnapshot>,
+        mode: &RegistrationMode<'_>,
+    ) -> Self {
+        let mut plan = Self::default();
+
+        for (target, cmds) in group_by_target(entries) {
+            if mode.bulk_overwrite {
+                plan.push(
+                    target,
+                    PlannedChange::Overwrite {
+                        names: cmds.iter().map(|cmd| cmd.name.to_string()).collect(),
+                    },
+                );
+            }
+
+            for cmd in &cmds {
+                let command_id = if mode.bulk_overwrite {
+                    remote
+                        .and_then(|r| r.command(target, cmd.name))
+                        .map(|r| r.id)
+                } else {
+                    plan.plan_command(target, cmd, remote)
+                };
+                plan.plan_permissions(target, cmd, remote, command_id);
+            }
+
+            if mode.bulk_overwrite {
+                let local: HashSet<_> = cmds.iter().map(|cmd| cmd.name).collect();
+                let kept = kept_names(target, mode.keep, mode.invalid);
+                plan.plan_stale(target, remote, |name| {
+                    local.contains(name) || kept.contains(name)
+                });
+            }
+        }
+
+        if mode.bulk_overwrite {
+            for &target in mode.removed {
+                let kept = kept_names(target, mode.keep, mode.invalid);
+                plan.plan_stale(target, remote, |name| kept.contains(name));
+            }
+        }
+
+        if let (Some(to_add), Some(remote)) = (mode.prune, remote) {
+            for &target in remote.commands.keys() {
+                plan.plan_stale(target, Some(remote), |name| {
+                    mode.keep.contains(name)
+                        || to_add
+                            .iter()
+                            .any(|cmd| cmd.name == name && cmd.targets().contains(&target))
+                });
+            }
+        }
+
+        plan
+    }
+
+    /// Plans to create or update the command, and returns the ID of its
+    /// remote copy.
+    fn plan_command(
+        &mut self,
+        target: CommandTarget,
+        cmd: &SlashCommandEntry,
+        remote: Option<&RemoteSnapshot>,
+    ) -> Option<CommandId> {
+        let name = cmd.name.to_string();
+        match remote.and_then(|r| r.command(target, cmd.name)) {
+            None => {
+                self.push(target, PlannedChange::Create { name });
+                None
+            }
+            Some(r) if remote_definition(r) == local_definition(&cmd.create) => {
+                self.unchanged.push(SyncedCommand { name, target });
+                Some(r.id)
+            }
+            Some(r) => {
+                self.push(
+                    target,
+                    PlannedChange::Update {
+                        name,
+                        command_id: r.id,
+                    },
+                );
+                Some(r.id)
+            }
+        }
+    }
+
+    fn plan_permissions(
+        &mut self,
+        target: CommandTarget,
+        cmd: &SlashCommandEntry,
+        remote: Option<&RemoteSnapshot>,
+        command_id: Option<CommandId>,
+    ) {
+        if !cmd.has_permissions() {
+            return;
+        }
+
+        let guilds = match (target, remote) {
+            (CommandTarget::Guild(guild_id), _) => vec![guild_id],
+            (CommandTarget::Global, Some(remote)) => remote.guilds.clone(),
+            (CommandTarget::Global, None) => Vec::new(),
+        };
+
+        for guild_id in guilds {
+            let unchanged = match (remote, command_id) {
+                (Some(remote), Some(command_id)) => {
+                    remote.permissions_match(guild_id, command_id, cmd)
+                }
+                _ => false,
+            };
+
+            if !unchanged {
+                self.push(
+                    CommandTarget::Guild(guild_id),
+                    PlannedChange::SetPermissions {
+                        name: cmd.name.to_string(),
+                        global: target == CommandTarget::Global,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Plans to delete the remote commands of the target that `keep` does not
+    /// match, unless they are already planned to be deleted.
+    fn plan_stale(
+        &mut self,
+        target: CommandTarget,
+        remote: Option<&RemoteSnapshot>,
+        keep: impl Fn(&str) -> bool,
+    ) {
+        let stale = remote
+            .and_then(|r| r.commands.get(&target))
+            .into_iter()
+            .flatten()
+            .filter(|r| !keep(&r.name));
+
+        for stale in stale {
+            let change = PlannedChange::Delete {
+                name: stale.name.clone(),
+                command_id: stale.id,
+            };
+            if !self
+                .changes
+                .get(&target)
+                .is_some_and(|c| c.contains(&change))
+            {
+                self.push(target, change);
+            }
+        }
+    }
+
+    fn push(&mut self, target: CommandTarget, change: PlannedChange) {
+        self.changes.entry(target).or_default().push(change);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub(crate) fn summary(&self) -> SyncSummary {
+        let mut summary = SyncSummary {
+            unchanged: self.unchanged.clone(),
+            ..Default::default()
+        };
+
+        for (&target, changes) in &self.changes {
+            for change in changes {
+                let (list, name) = match change {
+                    PlannedChange::Create { name } => (&mut summary.created, name),
+                    PlannedChange::Update { name, .. } => (&mut summary.updated, name),
+                    PlannedChange::Delete { name, .. } => (&mut summary.deleted, name),
+                    PlannedChange::SetPermissions { .. } | PlannedChange::Overwrite { .. } => {
+                        continue
+                    }
+                };
+                list.push(SyncedCommand {
+                    name: name.clone(),
+                    target,
+                });
+            }
+        }
+
+        summary
+    }
+}
+
+impl fmt::Display for RegistrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            f.write_str("no changes")?;
+        }
+
+        let mut first = true;
+        for (target, changes) in &self.changes {
+            if !first {
+                f.write_str("\n")?;
+            }
+            first = false;
+
+            write!(f, "{}:", target)?;
+            for change in changes {
+                write!(f, "\n  {}", change)?;
+            }
+        }
+
+        for target in &self.skipped {
+            write!(f, "\nskipped unchanged {}", target)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use serenity::builder::CreateApplicationCommand;
+
+    fn entry(name: &'static str, description: &str, guilds: Option<&[u64]>) -> SlashCommandEntry {
+        let mut create = CreateApplicationCommand::default();
+        create.name(name).description(description);
+
+        SlashCommandEntry {
+            name,
+            guilds: guilds.map(|guilds| guilds.iter().copied().map(GuildId).collect()),
+            create,
+            permissions: Default::default(),
+            deferred_permissions: Default::default(),
+        }
+    }
+
+    fn command(id: u64, name: &str, description: &str) -> ApplicationCommand {
+        serde_json::from_value(json!({
+            "id": id.to_string(),
+            "type": 1,
+            "application_id": "1",
+            "name": name,
+            "description": description,
+            "version": "1",
+        }))
+        .unwrap()
+    }
+
+    fn snapshot(commands: Vec<(CommandTarget, Vec<ApplicationCommand>)>) -> RemoteSnapshot {
+        RemoteSnapshot {
+            commands: commands.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn guild(guild_id: u64) -> CommandTarget {
+        CommandTarget::Guild(GuildId(guild_id))
+    }
+
+    fn create(name: &str) -> PlannedChange {
+        PlannedChange::Create {
+            name: name.to_string(),
+        }
+    }
+
+    fn update(name: &str, command_id: u64) -> PlannedChange {
+        PlannedChange::Update {
+            name: name.to_string(),
+            command_id: CommandId(command_id),
+        }
+    }
+
+    fn delete(name: &str, command_id: u64) -> PlannedChange {
+        PlannedChange::Delete {
+            name: name.to_string(),
+            command_id: CommandId(command_id),
+        }
+    }
+
+    fn mode<'a>(
+        bulk_overwrite: bool,
+        prune: Option<&'a [SlashCommandEntry]>,
+        keep: &'a HashSet<String>,
+        removed: &'a BTreeSet<CommandTarget>,
+    ) -> RegistrationMode<'a> {
+        RegistrationMode {
+            bulk_overwrite,
+            prune,
+            keep,
+            invalid: &[],
+            removed,
+        }
+    }
+
+    #[test]
+    fn everything_is_created_without_a_snapshot() {
+        let entries = [entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))];
+        let plan = RegistrationPlan::new(&entries, None, &HashSet::new());
+
+        assert_eq!(plan.changes[&CommandTarget::Global], [create("ping")]);
+        assert_eq!(plan.changes[&guild(1)], [create("ban")]);
+        assert!(plan.unchanged.is_empty());
+    }
+
+    #[test]
+    fn sync_plan_diffs_against_the_snapshot() {
+        let entries = [
+            entry("ping", "Ping", None),
+            entry("echo", "Echo", None),
+            entry("ban", "Ban", None),
+        ];
+        let remote = snapshot(vec![(
+            CommandTarget::Global,
+            vec![
+                command(1, "ping", " Ping "),
+                command(2, "echo", "Old echo"),
+                command(3, "stale", "Stale"),
+                command(4, "other", "Kept"),
+            ],
+        )]);
+        let keep = HashSet::from(["other".to_string()]);
+
+        let plan = RegistrationPlan::new(&entries, Some(&remote), &keep);
+
+        assert_eq!(
+            plan.changes[&CommandTarget::Global],
+            [update("echo", 2), create("ban"), delete("stale", 3)]
+        );
+        assert_eq!(
+            plan.unchanged,
+            [SyncedCommand {
+                name: "ping".to_string(),
+                target: CommandTarget::Global,
+            }]
+        );
+    }
+
+    #[test]
+    fn registration_only_deletes_when_pruning() {
+        let entries = [entry("ping", "Ping", None)];
+        let remote = snapshot(vec![
+            (CommandTarget::Global, vec![command(1, "stale", "Stale")]),
+            (guild(1), vec![command(2, "other", "Other")]),
+        ]);
+        let keep = HashSet::new();
+        let removed = BTreeSet::new();
+
+        let plan = RegistrationPlan::registration(
+            &entries,
+            Some(&remote),
+            &mode(false, None, &keep, &removed),
+        );
+        assert_eq!(plan.changes.len(), 1);
+        assert_eq!(plan.changes[&CommandTarget::Global], [create("ping")]);
+
+        let plan = RegistrationPlan::registration(
+            &entries,
+            Some(&remote),
+            &mode(false, Some(&entries), &keep, &removed),
+        );
+        assert_eq!(
+            plan.changes[&CommandTarget::Global],
+            [create("ping"), delete("stale", 1)]
+        );
+        assert_eq!(plan.changes[&guild(1)], [delete("other", 2)]);
+    }
+
+    #[test]
+    fn pruning_keeps_commands_of_skipped_targets() {
+        // The guild command is unchanged, so only the global one is sent, but
+        // the full set is still pruned against.
+        let to_add = [entry("ping", "Ping", None), entry("ban", "Ban", Some(&[1]))];
+        let remote = snapshot(vec![
+            (CommandTarget::Global, vec![command(1, "ping", "Ping")]),
+            (guild(1), vec![command(2, "ban", "Ban")]),
+        ]);
+        let keep = HashSet::new();
+        let removed = BTreeSet::new();
+
+        let plan = RegistrationPlan::registration(
+            &to_add[..1],
+            Some(&remote),
+            &mode(false, Some(&to_add), &keep, &removed),
+        );
+
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn bulk_mode_overwrites_whole_targets() {
+        let entries = [entry("ping", "Ping", None), entry("echo", "Echo", None)];
+        let remote = snapshot(vec![
+            (
+                CommandTarget::Global,
+                vec![
+                    command(1, "ping", "Old ping"),
+                    command(2, "stale", "Stale"),
+                    command(3, "other", "Kept"),
+                ],
+            ),
+            (
+                guild(1),
+                vec![command(4, "gone", "Gone"), command(5, "other", "Kept")],
+            ),
+            (guild(2), vec![command(6, "untouched", "Untouched")]),
+        ]);
+        let keep = HashSet::from(["other".to_string()]);
+        let removed = BTreeSet::from([guild(1)]);
+
+        let plan = RegistrationPlan::registration(
+            &entries,
+            Some(&remote),
+            &mode(true, None, &keep, &removed),
+        );
+
+        assert_eq!(
+            plan.changes[&CommandTarget::Global],
+            [
+                PlannedChange::Overwrite {
+                    names: vec!["ping".to_string(), "echo".to_string()],
+                },
+                delete("stale", 2),
+            ]
+        );
+        assert_eq!(plan.changes[&guild(1)], [delete("gone", 4)]);
+        assert!(!plan.changes.contains_key(&guild(2)));
+        assert!(plan.unchanged.is_empty());
+    }
+
+    #[test]
+    fn bulk_mode_keeps_remote_copies_of_invalid_entries() {
+        let entries = [entry("ping", "Ping", None)];
+        // Both are missing a description, and the guild has no valid
+        // commands left, so it is cleared.
+        let invalid = [entry("echo", "", None), entry("ban", "", Some(&[1]))];
+        let remote = snapshot(vec![
+            (
+                CommandTarget::Global,
+                vec![command(1, "ping", "Ping"), command(2, "echo", "Echo")],
+            ),
+            (
+                guild(1),
+                vec![command(3, "ban", "Ban"), command(4, "gone", "Gone")],
+            ),
+        ]);
+        let keep = HashSet::new();
+        let removed = BTreeSet::from([guild(1)]);
+        let mode = RegistrationMode {
+            invalid: &invalid,
+            ..mode(true, None, &keep, &removed)
+        };
+
+        let plan = RegistrationPlan::registration(&entries, Some(&remote), &mode);
+
+        assert_eq!(
+            plan.changes[&CommandTarget::Global],
+            [PlannedChange::Overwrite {
+                names: vec!["ping".to_string()],
+            }]
+        );
+        assert_eq!(plan.changes[&guild(1)], [delete("gone", 4)]);
+    }
+
+    #[test]
+    fn invalid_entries_are_only_kept_in_their_targets() {
+        let keep = HashSet::from(["other".to_string()]);
+        let invalid = [entry("ban", "", Some(&[1]))];
+
+        assert_eq!(
+            kept_names(guild(1), &keep, &invalid),
+            HashSet::from(["other".to_string(), "ban".to_string()])
+        );
+        assert_eq!(kept_names(guild(2), &keep, &invalid), keep);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{CommandTarget, SyncedCommand, UnresolvedRole};
+use serenity::{
+    model::id::{CommandId, GuildId},
+    Error,
+};
+use std::{fmt, io, sync::Arc};
+
+#[derive(Debug, Clone)]
+pub enum RegistrationOutcome {
+    Success(CommandId),
+    /// Includes Discord rejecting the command, with the reason it gave.
+    HttpError(Arc<Error>),
+    /// Found locally, before anything was sent.
+    ValidationError(String),
+    /// Discord accepted the bulk overwrite, but left the command out of the
+    /// commands it sent back.
+    MissingFromResponse,
+}
+
+impl RegistrationOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, RegistrationOutcome::Success(_))
+    }
+}
+
+impl From<Error> for RegistrationOutcome {
+    fn from(error: Error) -> Self {
+        RegistrationOutcome::HttpError(Arc::new(error))
+    }
+}
+
+impl fmt::Display for RegistrationOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistrationOutcome::Success(id) => write!(f, "registered as {}", id.0),
+            RegistrationOutcome::HttpError(error) => write!(f, "HTTP error: {}", error),
+            RegistrationOutcome::ValidationError(message) => {
+                write!(f, "validation error: {}", message)
+            }
+            RegistrationOutcome::MissingFromResponse => {
+                write!(f, "missing from the bulk overwrite response")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandRegistration {
+    pub name: String,
+    pub target: CommandTarget,
+    pub outcome: RegistrationOutcome,
+}
+
+/// Setting the permissions of a command that was registered failed in one
+/// guild.
+#[derive(Debug, Clone)]
+pub struct PermissionFailure {
+    pub command: String,
+    pub target: CommandTarget,
+    pub guild_id: GuildId,
+    pub error: Arc<Error>,
+}
+
+impl fmt::Display for PermissionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{} ({}): setting permissions in guild {} failed: {}",
+            self.command, self.target, self.guild_id.0, self.error
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RegistrationReport {
+    pub registrations: Vec<CommandRegistration>,
+    pub pruned: Vec<SyncedCommand>,
+    pub prune_error: Option<Error>,
+    pub unresolved_roles: Vec<UnresolvedRole>,
+    pub permission_failures: Vec<PermissionFailure>,
+    /// Targets whose commands did not change since they were last registered.
+    pub skipped: Vec<CommandTarget>,
+    pub hash_store_error: Option<io::Error>,
+}
+
+impl RegistrationReport {
+    pub fn push<O: Into<RegistrationOutcome>>(
+        &mut self,
+        name: &str,
+        target: CommandTarget,
+        outcome: O,
+    ) {
+        self.registrations.push(CommandRegistration {
+            name: name.to_string(),
+            target,
+            outcome: outcome.into(),
+        });
+    }
+
+    /// Replaces the outcome of an earlier registration, e.g. when setting its
+    /// permissions failed after the command itself was created.
+    pub(crate) fn fail(&mut self, name: &str, target: CommandTarget, outcome: RegistrationOutcome) {
+        match self
+            .registrations
+            .iter_mut()
+            .find(|r| r.name == name && r.target == target)
+        {
+            Some(registration) => registration.outcome = outcome,
+            None => self.push(name, target, outcome),
+        }
+    }
+
+    pub fn extend(&mut self, other: RegistrationReport) {
+        self.registrations.extend(other.registrations);
+        self.pruned.extend(other.pruned);
+        self.unresolved_roles.extend(other.unresolved_roles);
+        self.permission_failures.extend(other.permission_failures);
+        self.skipped.extend(other.skipped);
+        if self.prune_error.is_none() {
+            self.prune_error = other.prune_error;
+        }
+        if self.hash_store_error.is_none() {
+            self.hash_store_error = other.hash_store_error;
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.prune_error.is_none()
+            && self.permission_failures.is_empty()
+            && self.registrations.iter().all(|r| r.outcome.is_success())
+    }
+
+    pub fn successes(&self) -> impl Iterator<Item = &CommandRegistration> {
+        self.registrations.iter().filter(|r| r.outcome.is_success())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CommandRegistration> {
+        self.registrations
+            .iter()
+            .filter(|r| !r.outcome.is_success())
+    }
+}
+
+impl fmt::Display for RegistrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} registrations succeeded",
+            self.successes().count(),
+            self.registrations.len()
+        )?;
+
+        for failure in self.failures() {
+            write!(
+                f,
+                "\n  /{} ({}): {}",
+                failure.name, failure.target, failure.outcome
+            )?;
+        }
+
+        for failure in &self.permission_failures {
+            write!(f, "\n  {}", failure)?;
+        }
+
+        for unresolved in &self.unresolved_roles {
+            write!(f, "\n  {}", unresolved)?;
+        }
+
+        for pruned in &self.pruned {
+            write!(f, "\n  pruned {}", pruned)?;
+        }
+
+        if let Some(error) = &self.prune_error {
+            write!(f, "\n  pruning failed: {}", error)?;
+        }
+
+        for target in &self.skipped {
+            write!(f, "\n  skipped unchanged {}", target)?;
+        }
+
+        if let Some(error) = &self.hash_store_error {
+            write!(f, "\n  saving command hashes failed: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Check, Cooldown, SlashCommandCallback, SubcommandCallback};
+use serenity::model::interactions::application_command::{
+    ApplicationCommandInteractionData, ApplicationCommandInteractionDataOption,
+    ApplicationCommandOptionType,
+};
+use std::collections::HashMap;
+
+/// The callbacks of one command: one for the command itself, and one per
+/// subcommand keyed by its full path, e.g. `admin user ban`.
+#[derive(Debug, Clone, Default)]
+pub struct CommandCallbacks {
+    pub callback: Option<SlashCommandCallback>,
+    pub subcommands: HashMap<String, SubcommandCallback>,
+    pub checks: Vec<Check>,
+    pub cooldown: Option<Cooldown>,
+}
+
+impl From<SlashCommandCallback> for CommandCallbacks {
+    fn from(callback: SlashCommandCallback) -> Self {
+        Self {
+            callback: Some(callback),
+            subcommands: HashMap::new(),
+            checks: Vec::new(),
+            cooldown: None,
+        }
+    }
+}
+
+/// Walks the subcommand groups and subcommands of an interaction, returning
+/// the full path of the invoked subcommand and the options given to it.
+/// Commands without subcommands return their name and all options.
+pub fn subcommand_path(
+    data: &ApplicationCommandInteractionData,
+) -> (String, &[ApplicationCommandInteractionDataOption]) {
+    let mut path = data.name.clone();
+    let mut options = data.options.as_slice();
+
+    while let [option] = options {
+        match option.kind {
+            ApplicationCommandOptionType::SubCommandGroup
+            | ApplicationCommandOptionType::SubCommand => {
+                path.push(' ');
+                path.push_str(&option.name);
+                options = &option.options;
+            }
+            _ => break,
+        }
+    }
+
+    (path, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn data(options: Value) -> ApplicationCommandInteractionData {
+        serde_json::from_value(json!({
+            "id": "1",
+            "name": "admin",
+            "type": 1,
+            "options": options,
+        }))
+        .unwrap()
+    }
+
+    fn names(options: &[ApplicationCommandInteractionDataOption]) -> Vec<&str> {
+        options.iter().map(|option| option.name.as_str()).collect()
+    }
+
+    #[test]
+    fn plain_command_keeps_its_options() {
+        let data = data(json!([
+            { "type": 3, "name": "reason", "value": "spam" },
+            { "type": 4, "name": "days", "value": 7 },
+        ]));
+        let (path, options) = subcommand_path(&data);
+
+        assert_eq!(path, "admin");
+        assert_eq!(names(options), ["reason", "days"]);
+    }
+
+    #[test]
+    fn plain_command_without_options() {
+        let data = data(json!([]));
+        let (path, options) = subcommand_path(&data);
+
+        assert_eq!(path, "admin");
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn subcommand_gets_its_own_options() {
+        let data = data(json!([{
+            "type": 1,
+            "name": "ban",
+            "options": [{ "type": 3, "name": "reason", "value": "spam" }],
+        }]));
+        let (path, options) = subcommand_path(&data);
+
+        assert_eq!(path, "admin ban");
+        assert_eq!(names(options), ["reason"]);
+        assert_eq!(options[0].value, Some(json!("spam")));
+    }
+
+    #[test]
+    fn subcommand_group_walks_to_the_subcommand() {
+        let data = data(json!([{
+            "type": 2,
+            "name": "user",
+            "options": [{
+                "type": 1,
+                "name": "ban",
+                "options": [
+                    { "type": 3, "name": "reason", "value": "spam" },
+                    { "type": 4, "name": "days", "value": 7 },
+                ],
+            }],
+        }]));
+        let (path, options) = subcommand_path(&data);
+
+        assert_eq!(path, "admin user ban");
+        assert_eq!(names(options), ["reason", "days"]);
+    }
+
+    #[test]
+    fn single_plain_option_is_not_a_subcommand() {
+        let data = data(json!([{ "type": 3, "name": "reason", "value": "spam" }]));
+        let (path, options) = subcommand_path(&data);
+
+        assert_eq!(path, "admin");
+        assert_eq!(names(options), ["reason"]);
+    }
+}
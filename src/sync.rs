@@ -0,0 +1,359 @@
+use crate::{
+    bulk::group_by_target, handler::CommandIds, permissions::resolve_permissions, CommandTarget,
+    PlannedChange, RegistrationPlan, RemoteSnapshot, SlashCommandEntry, UnresolvedRole,
+};
+use serde_json::{json, Value};
+use serenity::{
+    builder::CreateApplicationCommand, client::Context,
+    model::interactions::application_command::ApplicationCommand, Result,
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedCommand {
+    pub name: String,
+    pub target: CommandTarget,
+}
+
+impl fmt::Display for SyncedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{} ({})", self.name, self.target)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub created: Vec<SyncedCommand>,
+    pub updated: Vec<SyncedCommand>,
+    pub deleted: Vec<SyncedCommand>,
+    pub unchanged: Vec<SyncedCommand>,
+    pub unresolved_roles: Vec<UnresolvedRole>,
+}
+
+impl SyncSummary {
+    pub fn has_changes(&self) -> bool {
+        !(self.created.is_empty() && self.updated.is_empty() && self.deleted.is_empty())
+    }
+}
+
+impl fmt::Display for SyncSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} deleted, {} unchanged",
+            self.created.len(),
+            self.updated.len(),
+            self.deleted.len(),
+            self.unchanged.len()
+        )?;
+
+        for (label, commands) in [
+            ("created", &self.created),
+            ("updated", &self.updated),
+            ("deleted", &self.deleted),
+        ] {
+            for command in commands {
+                write!(f, "\n  {} {}", label, command)?;
+            }
+        }
+
+        for unresolved in &self.unresolved_roles {
+            write!(f, "\n  {}", unresolved)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn local_definition(create: &CreateApplicationCommand) -> Value {
+    let map = create
+        .0
+        .iter()
+        .map(|(&k, v)| (k.to_string(), v.clone()))
+        .collect();
+    normalize_command(&Value::Object(map))
+}
+
+pub(crate) fn remote_definition(command: &ApplicationCommand) -> Value {
+    normalize_command(&serde_json::to_value(command).unwrap_or_default())
+}
+
+// Discord omits fields that are set to their default value, so both sides are
+// brought into the same shape before comparing them.
+fn normalize_command(value: &Value) -> Value {
+    json!({
+        "type": field_or(value, "type", json!(1)),
+        "name": field_or(value, "name", json!("")),
+        "description": trimmed(field_or(value, "description", json!(""))),
+        "default_permission": field_or(value, "default_permission", json!(true)),
+        "options": normalize_options(value.get("options")),
+    })
+}
+
+fn normalize_options(options: Option<&Value>) -> Value {
+    options
+        .and_then(Value::as_array)
+        .map(|options| options.iter().map(normalize_option).collect())
+        .unwrap_or_else(|| json!([]))
+}
+
+fn normalize_option(value: &Value) -> Value {
+    json!({
+        "type": field_or(value, "type", Value::Null),
+        "name": field_or(value, "name", json!("")),
+        "description": trimmed(field_or(value, "description", json!(""))),
+        "required": field_or(value, "required", json!(false)),
+        "choices": field_or(value, "choices", json!([])),
+        "options": normalize_options(value.get("options")),
+        "channel_types": field_or(value, "channel_types", json!([])),
+        "min_value": field_or(value, "min_value", Value::Null),
+        "max_value": field_or(value, "max_value", Value::Null),
+    })
+}
+
+fn field_or(value: &Value, key: &str, default: Value) -> Value {
+    match value.get(key) {
+        Some(Value::Null) | None => default,
+        Some(v) => v.clone(),
+    }
+}
+
+fn trimmed(value: Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.trim().to_string()),
+        v => v,
+    }
+}
+
+pub(crate) async fn sync_application_commands(
+    ctx: &Context,
+    entries: &[SlashCommandEntry],
+    keep: &HashSet<String>,
+    skip_global: bool,
+    command_ids: &mut CommandIds,
+) -> Result<SyncSummary> {
+    let mut remote = RemoteSnapshot::fetch(ctx, entries).await?;
+    if skip_global {
+        remote.commands.remove(&CommandTarget::Global);
+    }
+    let plan = RegistrationPlan::new(entries, Some(&remote), keep);
+
+    let unresolved_roles = execute_plan(ctx, entries, &plan, &remote, command_ids).await?;
+
+    Ok(SyncSummary {
+        unresolved_roles,
+        ..plan.summary()
+    })
+}
+
+async fn execute_plan(
+    ctx: &Context,
+    entries: &[SlashCommandEntry],
+    plan: &RegistrationPlan,
+    remote: &RemoteSnapshot,
+    command_ids: &mut CommandIds,
+) -> Result<Vec<UnresolvedRole>> {
+    let mut unresolved = Vec::new();
+    let find = |target: CommandTarget, name: &str| {
+        entries
+            .iter()
+            .find(|cmd| cmd.name == name && cmd.targets().contains(&target))
+    };
+
+    for (&target, commands) in &remote.commands {
+        for command in commands {
+            if find(target, &command.name).is_some() {
+                command_ids.insert((command.name.clone(), target), command.id);
+            }
+        }
+    }
+
+    for (&target, changes) in &plan.changes {
+        for change in changes {
+            match change {
+                PlannedChange::Create { name } => {
+                    if let Some(cmd) = find(target, name) {
+                        let command = target.create_command(ctx, &cmd.create).await?;
+                        command_ids.insert((command.name, target), command.id);
+                    }
+                }
+                PlannedChange::Update { name, command_id } => {
+                    if let Some(cmd) = find(target, name) {
+                        target.edit_command(ctx, *command_id, &cmd.create).await?;
+                    }
+                }
+                PlannedChange::Delete { command_id, .. } => {
+                    target.delete_command(ctx, *command_id).await?;
+                }
+                // Only registration plans overwrite targets.
+                PlannedChange::Overwrite { .. } => {}
+                PlannedChange::SetPermissions { name, global } => {
+                    let guild_id = match target {
+                        CommandTarget::Guild(guild_id) => guild_id,
+                        CommandTarget::Global => continue,
+                    };
+                    let command_target = if *global {
+                        CommandTarget::Global
+                    } else {
+                        target
+                    };
+
+                    let cmd = find(command_target, name);
+                    let command_id = command_ids.get(&(name.clone(), command_target));
+                    if let (Some(cmd), Some(&command_id)) = (cmd, command_id) {
+                        let (permissions, missing) = resolve_permissions(ctx, cmd, guild_id).await;
+                        unresolved.extend(missing);
+                        guild_id
+                            .create_application_command_permission(ctx, command_id, move |p| {
+                                p.0 = permissions.0;
+                                p
+                            })
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(unresolved)
+}
+
+/// Deletes the remote commands that are not in `entries`. With `skip_global`,
+/// global commands are left alone, e.g. while they are registered in a dev
+/// guild instead.
+pub(crate) async fn prune_stale_commands(
+    ctx: &Context,
+    entries: &[SlashCommandEntry],
+    keep: &HashSet<String>,
+    skip_global: bool,
+) -> Result<Vec<SyncedCommand>> {
+    let mut targets: BTreeSet<_> = group_by_target(entries).into_keys().collect();
+    targets.insert(CommandTarget::Global);
+    targets.extend(
+        ctx.cache
+            .guilds()
+            .await
+            .into_iter()
+            .map(CommandTarget::Guild),
+    );
+    if skip_global {
+        targets.remove(&CommandTarget::Global);
+    }
+
+    let mut deleted = Vec::new();
+
+    for target in targets {
+        let is_local = |name: &str| {
+            entries
+                .iter()
+                .any(|cmd| cmd.name == name && cmd.targets().contains(&target))
+        };
+
+        for stale in target
+            .get_commands(ctx)
+            .await?
+            .into_iter()
+            .filter(|r| !keep.contains(&r.name) && !is_local(&r.name))
+        {
+            target.delete_command(ctx, stale.id).await?;
+            deleted.push(SyncedCommand {
+                name: stale.name,
+                target,
+            });
+        }
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::model::interactions::application_command::ApplicationCommandOptionType;
+
+    #[test]
+    fn missing_fields_are_filled_with_their_defaults() {
+        let normalized = normalize_command(&json!({
+            "name": "ping",
+            "description": "Ping ",
+            "options": [{ "type": 3, "name": "message", "description": "Message" }],
+        }));
+
+        assert_eq!(
+            normalized,
+            json!({
+                "type": 1,
+                "name": "ping",
+                "description": "Ping",
+                "default_permission": true,
+                "options": [{
+                    "type": 3,
+                    "name": "message",
+                    "description": "Message",
+                    "required": false,
+                    "choices": [],
+                    "options": [],
+                    "channel_types": [],
+                    "min_value": null,
+                    "max_value": null,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn nulls_count_as_missing() {
+        let explicit = json!({
+            "type": 1,
+            "name": "ping",
+            "description": "Ping",
+            "default_permission": true,
+            "options": [],
+        });
+        let nulls = json!({
+            "type": null,
+            "name": "ping",
+            "description": "Ping",
+            "default_permission": null,
+            "options": null,
+        });
+
+        assert_eq!(normalize_command(&explicit), normalize_command(&nulls));
+    }
+
+    #[test]
+    fn local_and_remote_definitions_match() {
+        let mut create = CreateApplicationCommand::default();
+        create
+            .name("ping")
+            .description("Ping")
+            .create_option(|option| {
+                option
+                    .name("message")
+                    .description("Message")
+                    .kind(ApplicationCommandOptionType::String)
+                    .required(true)
+            });
+
+        let remote: ApplicationCommand = serde_json::from_value(json!({
+            "id": "1",
+            "type": 1,
+            "application_id": "1",
+            "name": "ping",
+            "description": "Ping",
+            "options": [{
+                "type": 3,
+                "name": "message",
+                "description": "Message",
+                "required": true,
+            }],
+            "version": "1",
+        }))
+        .unwrap();
+
+        assert_eq!(local_definition(&create), remote_definition(&remote));
+    }
+}
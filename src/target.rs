@@ -0,0 +1,129 @@
+use crate::{
+    permissions::resolve_permissions, PermissionFailure, SlashCommandEntry, UnresolvedRole,
+};
+use serenity::{
+    builder::CreateApplicationCommand,
+    client::Context,
+    model::{
+        id::{CommandId, GuildId},
+        interactions::application_command::ApplicationCommand,
+    },
+    Result,
+};
+use std::{fmt, sync::Arc};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CommandTarget {
+    Global,
+    Guild(GuildId),
+}
+
+impl CommandTarget {
+    pub(crate) async fn get_commands(self, ctx: &Context) -> Result<Vec<ApplicationCommand>> {
+        match self {
+            CommandTarget::Global => ApplicationCommand::get_global_application_commands(ctx).await,
+            CommandTarget::Guild(guild_id) => guild_id.get_application_commands(ctx).await,
+        }
+    }
+
+    pub(crate) async fn create_command(
+        self,
+        ctx: &Context,
+        create: &CreateApplicationCommand,
+    ) -> Result<ApplicationCommand> {
+        let f = replace_with(create.clone());
+        match self {
+            CommandTarget::Global => {
+                ApplicationCommand::create_global_application_command(ctx, f).await
+            }
+            CommandTarget::Guild(guild_id) => guild_id.create_application_command(ctx, f).await,
+        }
+    }
+
+    pub(crate) async fn edit_command(
+        self,
+        ctx: &Context,
+        command_id: CommandId,
+        create: &CreateApplicationCommand,
+    ) -> Result<ApplicationCommand> {
+        let f = replace_with(create.clone());
+        match self {
+            CommandTarget::Global => {
+                ApplicationCommand::edit_global_application_command(ctx, command_id, f).await
+            }
+            CommandTarget::Guild(guild_id) => {
+                guild_id.edit_application_command(ctx, command_id, f).await
+            }
+        }
+    }
+
+    pub(crate) async fn delete_command(self, ctx: &Context, command_id: CommandId) -> Result<()> {
+        match self {
+            CommandTarget::Global => {
+                ApplicationCommand::delete_global_application_command(ctx, command_id).await
+            }
+            CommandTarget::Guild(guild_id) => {
+                guild_id.delete_application_command(ctx, command_id).await
+            }
+        }
+    }
+
+    /// The guilds in which permissions for a command of this target apply.
+    /// Global commands have no guild of their own, so their permissions have
+    /// to be applied in every guild the bot is currently in.
+    pub(crate) async fn permission_guilds(self, ctx: &Context) -> Vec<GuildId> {
+        match self {
+            CommandTarget::Global => ctx.cache.guilds().await,
+            CommandTarget::Guild(guild_id) => vec![guild_id],
+        }
+    }
+
+    /// Sets the permissions of a command in every guild they apply to, going
+    /// on with the other guilds when one fails.
+    pub(crate) async fn set_command_permissions(
+        self,
+        ctx: &Context,
+        command_id: CommandId,
+        cmd: &SlashCommandEntry,
+    ) -> (Vec<UnresolvedRole>, Vec<PermissionFailure>) {
+        let mut unresolved = Vec::new();
+        let mut failures = Vec::new();
+        for guild_id in self.permission_guilds(ctx).await {
+            let (permissions, missing) = resolve_permissions(ctx, cmd, guild_id).await;
+            unresolved.extend(missing);
+            if let Err(why) = guild_id
+                .create_application_command_permission(ctx, command_id, move |p| {
+                    p.0 = permissions.0;
+                    p
+                })
+                .await
+            {
+                failures.push(PermissionFailure {
+                    command: cmd.name.to_string(),
+                    target: self,
+                    guild_id,
+                    error: Arc::new(why),
+                });
+            }
+        }
+        (unresolved, failures)
+    }
+}
+
+fn replace_with(
+    create: CreateApplicationCommand,
+) -> impl FnOnce(&mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    move |c| {
+        c.0 = create.0;
+        c
+    }
+}
+
+impl fmt::Display for CommandTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandTarget::Global => f.write_str("global"),
+            CommandTarget::Guild(guild_id) => write!(f, "guild {}", guild_id.0),
+        }
+    }
+}
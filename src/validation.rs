@@ -0,0 +1,358 @@
+use crate::{RegistrationOutcome, RegistrationReport, SlashCommandEntry};
+use serde_json::Value;
+use thiserror::Error;
+
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+const MAX_OPTIONS: usize = 25;
+const MAX_CHOICES: usize = 25;
+const MAX_CHOICE_NAME_LENGTH: usize = 100;
+const MAX_CHOICE_VALUE_LENGTH: usize = 100;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("/{command}: `{field}` {reason}")]
+pub struct ValidationError {
+    pub command: String,
+    pub field: String,
+    pub reason: String,
+}
+
+struct Validator<'a> {
+    command: &'a str,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator<'_> {
+    fn error(&mut self, field: &str, reason: String) {
+        self.errors.push(ValidationError {
+            command: self.command.to_string(),
+            field: field.to_string(),
+            reason,
+        });
+    }
+
+    /// Checks the length of a name, and with `chat_input` also the characters
+    /// that chat input commands and options are limited to.
+    fn name(&mut self, field: &str, value: Option<&Value>, chat_input: bool) {
+        let name = value.and_then(Value::as_str).unwrap_or_default();
+        let len = name.chars().count();
+
+        if len == 0 || len > MAX_NAME_LENGTH {
+            self.error(
+                field,
+                format!("must be 1-{} characters long, got {}", MAX_NAME_LENGTH, len),
+            );
+        }
+
+        if !chat_input {
+            return;
+        }
+
+        // Letters without case, such as CJK, are allowed as well.
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !(c == '-' || c == '_' || (c.is_alphanumeric() && !c.is_uppercase())))
+        {
+            self.error(
+                field,
+                format!(
+                    "may only contain lowercase letters, digits, '-' and '_', found {:?}",
+                    c
+                ),
+            );
+        }
+    }
+
+    fn description(&mut self, field: &str, value: Option<&Value>) {
+        let description = value.and_then(Value::as_str).unwrap_or_default();
+        let len = description.trim().chars().count();
+
+        if len == 0 || len > MAX_DESCRIPTION_LENGTH {
+            self.error(
+                field,
+                format!(
+                    "must be 1-{} characters long, got {}",
+                    MAX_DESCRIPTION_LENGTH, len
+                ),
+            );
+        }
+    }
+
+    fn options(&mut self, field: &str, value: Option<&Value>) {
+        let options = match value.and_then(Value::as_array) {
+            Some(options) => options,
+            None => return,
+        };
+
+        if options.len() > MAX_OPTIONS {
+            self.error(
+                field,
+                format!(
+                    "may have at most {} options, got {}",
+                    MAX_OPTIONS,
+                    options.len()
+                ),
+            );
+        }
+
+        let mut seen_optional = false;
+        for (idx, option) in options.iter().enumerate() {
+            let field = format!("{}[{}]", field, idx);
+
+            self.name(&format!("{}.name", field), option.get("name"), true);
+            self.description(&format!("{}.description", field), option.get("description"));
+            self.choices(&format!("{}.choices", field), option.get("choices"));
+            self.options(&format!("{}.options", field), option.get("options"));
+
+            let required = option
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or_default();
+            if required && seen_optional {
+                self.error(
+                    &format!("{}.required", field),
+                    "required options must come before optional ones".to_string(),
+                );
+            }
+            seen_optional |= !required;
+        }
+    }
+
+    fn choices(&mut self, field: &str, value: Option<&Value>) {
+        let choices = match value.and_then(Value::as_array) {
+            Some(choices) => choices,
+            None => return,
+        };
+
+        if choices.len() > MAX_CHOICES {
+            self.error(
+                field,
+                format!(
+                    "may have at most {} choices, got {}",
+                    MAX_CHOICES,
+                    choices.len()
+                ),
+            );
+        }
+
+        for (idx, choice) in choices.iter().enumerate() {
+            let name = choice
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let len = name.chars().count();
+            if len == 0 || len > MAX_CHOICE_NAME_LENGTH {
+                self.error(
+                    &format!("{}[{}].name", field, idx),
+                    format!(
+                        "must be 1-{} characters long, got {}",
+                        MAX_CHOICE_NAME_LENGTH, len
+                    ),
+                );
+            }
+
+            if let Some(value) = choice.get("value").and_then(Value::as_str) {
+                let len = value.chars().count();
+                if len > MAX_CHOICE_VALUE_LENGTH {
+                    self.error(
+                        &format!("{}[{}].value", field, idx),
+                        format!(
+                            "must be at most {} characters long, got {}",
+                            MAX_CHOICE_VALUE_LENGTH, len
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Checks a command definition against Discord's limits, so that mistakes
+/// are caught before anything is sent.
+pub fn validate_entry(entry: &SlashCommandEntry) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        command: entry.name,
+        errors: Vec::new(),
+    };
+    let create = &entry.create.0;
+
+    // Only chat input commands, the default kind, have a description and
+    // restrict the characters of their name. User and message commands may
+    // contain uppercase letters and spaces.
+    let chat_input = create.get("type").and_then(Value::as_u64).unwrap_or(1) == 1;
+    validator.name("name", create.get("name"), chat_input);
+    if chat_input {
+        validator.description("description", create.get("description"));
+    }
+    validator.options("options", create.get("options"));
+
+    validator.errors
+}
+
+pub fn validate_entries(entries: &[SlashCommandEntry]) -> Vec<ValidationError> {
+    entries.iter().flat_map(validate_entry).collect()
+}
+
+/// Records a validation error in `report` for every target of an invalid
+/// entry, and splits the entries into the ones that can be sent to Discord
+/// and the invalid ones.
+pub(crate) fn reject_invalid(
+    entries: &[SlashCommandEntry],
+    report: &mut RegistrationReport,
+) -> (Vec<SlashCommandEntry>, Vec<SlashCommandEntry>) {
+    let mut valid = Vec::with_capacity(entries.len());
+    let mut invalid = Vec::new();
+
+    for entry in entries {
+        let errors = validate_entry(entry);
+        if errors.is_empty() {
+            valid.push(entry.clone());
+            continue;
+        }
+        invalid.push(entry.clone());
+
+        let message = errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        for target in entry.targets() {
+            report.push(
+                entry.name,
+                target,
+                RegistrationOutcome::ValidationError(message.clone()),
+            );
+        }
+    }
+
+    (valid, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest;
+    use serde_json::json;
+
+    fn entry(command: Value) -> SlashCommandEntry {
+        let create = manifest::command_from_json(command.as_object().unwrap()).unwrap();
+
+        SlashCommandEntry {
+            name: "test",
+            guilds: None,
+            create,
+            permissions: Default::default(),
+            deferred_permissions: Default::default(),
+        }
+    }
+
+    fn fields(command: Value) -> Vec<String> {
+        validate_entry(&entry(command))
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_chat_input_command() {
+        let command = json!({
+            "name": "ban-user_2",
+            "description": "Bans a user",
+            "options": [
+                { "type": 6, "name": "user", "description": "The user", "required": true },
+                { "type": 3, "name": "reason", "description": "Why" },
+            ],
+        });
+        assert!(fields(command).is_empty());
+    }
+
+    #[test]
+    fn rejects_uppercase_and_spaces_in_chat_input_names() {
+        assert_eq!(
+            fields(json!({ "name": "Ban", "description": "Ban" })),
+            ["name"]
+        );
+        assert_eq!(
+            fields(json!({ "name": "ban user", "description": "Ban" })),
+            ["name"]
+        );
+    }
+
+    #[test]
+    fn accepts_letters_without_case() {
+        let command = json!({ "name": "テスト", "description": "Test" });
+        assert!(fields(command).is_empty());
+    }
+
+    #[test]
+    fn allows_uppercase_and_spaces_in_user_and_message_commands() {
+        for kind in [2, 3] {
+            let command = json!({ "type": kind, "name": "Report Message" });
+            assert!(fields(command).is_empty());
+        }
+    }
+
+    #[test]
+    fn checks_name_length_of_every_command_type() {
+        let long = "a".repeat(MAX_NAME_LENGTH + 1);
+        assert_eq!(fields(json!({ "type": 2, "name": "" })), ["name"]);
+        assert_eq!(fields(json!({ "type": 3, "name": long })), ["name"]);
+    }
+
+    #[test]
+    fn requires_description_of_chat_input_commands() {
+        assert_eq!(fields(json!({ "name": "ping" })), ["description"]);
+        assert_eq!(
+            fields(json!({ "name": "ping", "description": "   " })),
+            ["description"]
+        );
+    }
+
+    #[test]
+    fn validates_nested_options() {
+        let command = json!({
+            "name": "admin",
+            "description": "Admin commands",
+            "options": [{
+                "type": 1,
+                "name": "Ban",
+                "description": "Bans a user",
+                "options": [
+                    { "type": 3, "name": "reason", "description": "Why" },
+                    { "type": 6, "name": "user", "description": "", "required": true },
+                ],
+            }],
+        });
+        assert_eq!(
+            fields(command),
+            [
+                "options[0].name",
+                "options[0].options[1].description",
+                "options[0].options[1].required",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_options_and_choices() {
+        let options: Vec<_> = (0..=MAX_OPTIONS)
+            .map(|i| json!({ "type": 3, "name": format!("o{}", i), "description": "Option" }))
+            .collect();
+        let command = json!({ "name": "many", "description": "Many", "options": options });
+        assert_eq!(fields(command), ["options"]);
+
+        let mut choices: Vec<_> = (0..=MAX_CHOICES)
+            .map(|i| json!({ "name": format!("c{}", i), "value": "x" }))
+            .collect();
+        choices[0]["value"] = json!("x".repeat(MAX_CHOICE_VALUE_LENGTH + 1));
+        let command = json!({
+            "name": "choose",
+            "description": "Choose",
+            "options": [{ "type": 3, "name": "c", "description": "C", "choices": choices }],
+        });
+        assert_eq!(
+            fields(command),
+            ["options[0].choices", "options[0].choices[0].value"]
+        );
+    }
+}